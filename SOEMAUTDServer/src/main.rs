@@ -2,24 +2,33 @@
 
 mod log_formatter;
 
-use std::num::{NonZeroU64, NonZeroUsize};
+use std::{
+    collections::BTreeMap,
+    num::{NonZeroU64, NonZeroUsize},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use log_formatter::LogFormatter;
 
 use autd3_driver::{
-    firmware::cpu::TxMessage,
+    autd3_device::AUTD3,
+    firmware::cpu::{RxMessage, TxMessage},
     link::{Link, LinkBuilder},
 };
+use autd3_firmware_emulator::CPUEmulator;
 use autd3_link_soem::{TimerStrategy, SOEM};
 use autd3_protobuf::*;
+use autd3_server_shutdown::shutdown_or_timeout;
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use thread_priority::{ThreadPriority, ThreadPriorityValue};
 
-use tokio::{
-    runtime::Runtime,
-    sync::{mpsc, RwLock},
-};
-use tonic::{transport::Server, Request, Response, Status};
+use tokio::sync::RwLock;
+use tonic::{codec::CompressionEncoding, transport::Server, Request, Response, Status};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum TimerStrategyArg {
@@ -31,6 +40,153 @@ enum TimerStrategyArg {
     SpinWait,
 }
 
+impl From<TimerStrategyArg> for TimerStrategy {
+    fn from(arg: TimerStrategyArg) -> Self {
+        match arg {
+            TimerStrategyArg::StdSleep => TimerStrategy::StdSleep,
+            TimerStrategyArg::SpinSleep => TimerStrategy::SpinSleep,
+            TimerStrategyArg::SpinWait => TimerStrategy::SpinWait,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum CompressionArg {
+    /// no compression
+    None,
+    /// gzip
+    Gzip,
+    /// zstd
+    Zstd,
+}
+
+impl CompressionArg {
+    fn encoding(self) -> Option<CompressionEncoding> {
+        match self {
+            CompressionArg::None => None,
+            CompressionArg::Gzip => Some(CompressionEncoding::Gzip),
+            CompressionArg::Zstd => Some(CompressionEncoding::Zstd),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ErrorPolicyArg {
+    /// exit the process
+    Exit,
+    /// log the error and continue
+    Continue,
+    /// log the error, continue and report it to the client until the slave is found again
+    Report,
+}
+
+/// Lost slaves and their errors, which are reported to the client in the report mode
+type LostSlaves = Arc<Mutex<BTreeMap<usize, String>>>;
+
+/// Create the error handler of the SOEM link, which applies `on_error` to a lost slave.
+///
+/// A lost slave is kept in `lost` until the link reports it in another state, i.e., until it is found again.
+fn err_handler(
+    on_error: ErrorPolicyArg,
+    lost: LostSlaves,
+    exit: impl Fn() + Send + Sync + 'static,
+) -> impl Fn(usize, autd3_link_soem::Status) + Send + Sync + 'static {
+    move |slave, status| {
+        tracing::error!("slave [{}]: {}", slave, status);
+        let mut lost = lost.lock().unwrap();
+        if status != autd3_link_soem::Status::Lost {
+            if lost.remove(&slave).is_some() {
+                tracing::info!("slave [{}] is found again", slave);
+            }
+            return;
+        }
+        match on_error {
+            ErrorPolicyArg::Exit => exit(),
+            ErrorPolicyArg::Continue => {}
+            ErrorPolicyArg::Report => {
+                lost.insert(slave, format!("slave [{}]: {}", slave, status));
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum LogFormatArg {
+    /// human readable text
+    Text,
+    /// JSON lines
+    Json,
+}
+
+fn parse_priority(s: &str) -> Result<ThreadPriority, String> {
+    match s {
+        "min" => Ok(ThreadPriority::Min),
+        "max" => Ok(ThreadPriority::Max),
+        _ => {
+            let value = s.parse::<u8>().map_err(|e| e.to_string())?;
+            Ok(ThreadPriority::Crossplatform(
+                ThreadPriorityValue::try_from(value).map_err(|e| e.to_string())?,
+            ))
+        }
+    }
+}
+
+fn parse_cpu_list(s: &str) -> Result<Vec<usize>, String> {
+    s.split(',')
+        .map(|part| match part.split_once('-') {
+            Some((start, end)) => {
+                let start = start.trim().parse::<usize>().map_err(|e| e.to_string())?;
+                let end = end.trim().parse::<usize>().map_err(|e| e.to_string())?;
+                if start > end {
+                    return Err(format!("invalid cpu range: {}", part));
+                }
+                Ok((start..=end).collect())
+            }
+            None => Ok(vec![part
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| e.to_string())?]),
+        })
+        .collect::<Result<Vec<Vec<_>>, _>>()
+        .map(|v| v.concat())
+}
+
+#[cfg(target_os = "linux")]
+fn check_cpus(cpus: &[usize], num_cpus: usize) -> Result<(), String> {
+    match cpus.iter().find(|&&cpu| cpu >= num_cpus) {
+        Some(cpu) => Err(format!(
+            "cpu {} is out of range, only {} cpus are online",
+            cpu, num_cpus
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Pin the whole process, i.e., the calling thread and all threads spawned after it
+#[cfg(target_os = "linux")]
+fn set_affinity(cpus: &[usize]) -> Result<(), String> {
+    let num_cpus = match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } {
+        n if n > 0 => n as usize,
+        _ => return Err(std::io::Error::last_os_error().to_string()),
+    };
+    // CPU_SET panics for a cpu out of the set
+    check_cpus(cpus, num_cpus.min(libc::CPU_SETSIZE as usize))?;
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        cpus.iter().for_each(|&cpu| libc::CPU_SET(cpu, &mut set));
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_: &[usize]) -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(
@@ -39,15 +195,18 @@ enum TimerStrategyArg {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Log output format
+    #[clap(long = "log_format", global = true, default_value = "text")]
+    log_format: LogFormatArg,
 }
 
 #[derive(Args)]
 struct Arg {
-    /// Interface name
-    #[clap(short = 'i', long = "ifname", default_value = "")]
+    /// Interface name (falls back to AUTD_IFNAME)
+    #[clap(short = 'i', long = "ifname", env = "AUTD_IFNAME", default_value = "")]
     ifname: String,
-    /// Client port
-    #[clap(short = 'p', long = "port")]
+    /// Client port (falls back to AUTD_SERVER_PORT)
+    #[clap(short = 'p', long = "port", env = "AUTD_SERVER_PORT")]
     port: u16,
     /// Sync0 cycle time in us
     #[clap(short = 's', long = "sync0", default_value = "1000")]
@@ -72,6 +231,55 @@ struct Arg {
     sync_timeout: u64,
     #[clap(short = 'l', long = "lightweight", default_value = "false")]
     lightweight: bool,
+    /// gRPC compression of the responses (compressed requests are always accepted)
+    #[clap(long = "compression", default_value = "none")]
+    compression: CompressionArg,
+    /// Priority of the SOEM thread (min, max, or 0-99)
+    #[clap(long = "priority", value_parser = parse_priority)]
+    priority: Option<ThreadPriority>,
+    /// CPUs the whole server process, including the SOEM threads, is pinned to (e.g. 0,2-3)
+    #[clap(long = "affinity", value_parser = parse_cpu_list)]
+    affinity: Option<Vec<usize>>,
+    /// What to do when a slave is lost
+    #[clap(long = "on_error", default_value = "exit")]
+    on_error: ErrorPolicyArg,
+    /// Shut down if no client connects within this time in s (0 means wait indefinitely)
+    #[clap(long = "connect_timeout", default_value = "0")]
+    connect_timeout: u64,
+    /// HTTP/2 keepalive ping interval in s (Optional, disabled if not set)
+    #[clap(long = "keepalive_interval")]
+    keepalive_interval: Option<u64>,
+    /// Timeout of the HTTP/2 keepalive ping in s (Optional, 20 s if not set)
+    #[clap(long = "keepalive_timeout")]
+    keepalive_timeout: Option<u64>,
+    /// Maximum number of requests processed at once on each client connection; the number of connections is not limited (Optional, unlimited if not set)
+    #[clap(long = "concurrency_limit_per_connection")]
+    concurrency_limit_per_connection: Option<usize>,
+    /// Run without EtherCAT hardware, using emulated devices
+    #[clap(long = "mock", default_value = "false")]
+    mock: bool,
+    /// Number of emulated devices in mock mode
+    #[clap(long = "mock_devices", default_value = "1")]
+    mock_devices: NonZeroUsize,
+}
+
+#[derive(Args)]
+struct SelftestArg {
+    /// Interface name (falls back to AUTD_IFNAME)
+    #[clap(short = 'i', long = "ifname", env = "AUTD_IFNAME", default_value = "")]
+    ifname: String,
+    /// Sync0 cycle time in us
+    #[clap(short = 's', long = "sync0", default_value = "1000")]
+    sync0: NonZeroU64,
+    /// Send cycle time in us
+    #[clap(short = 'c', long = "send", default_value = "1000")]
+    send: NonZeroU64,
+    /// Timer strategy
+    #[clap(short = 'w', long = "timer", default_value = "std-sleep")]
+    timer_strategy: TimerStrategyArg,
+    /// Sync timeout in s
+    #[clap(short = 'o', long = "sync_timeout", default_value = "10")]
+    sync_timeout: u64,
 }
 
 #[derive(Subcommand)]
@@ -79,11 +287,71 @@ enum Commands {
     Run(Arg),
     /// List available interfaces
     List,
+    /// Open the link once, report the found devices and exit
+    Selftest(SelftestArg),
+}
+
+enum Backend {
+    SOEM(SOEM),
+    Mock(Vec<CPUEmulator>),
+}
+
+impl Backend {
+    fn mock(num_dev: usize) -> Self {
+        Self::Mock(
+            (0..num_dev)
+                .map(|i| CPUEmulator::new(i, AUTD3::NUM_TRANS_IN_UNIT))
+                .collect(),
+        )
+    }
+
+    async fn send(&mut self, tx: &[TxMessage]) -> bool {
+        match self {
+            Self::SOEM(soem) => Link::send(soem, tx).await.unwrap_or(false),
+            Self::Mock(cpus) => {
+                cpus.iter_mut().for_each(|cpu| cpu.send(tx));
+                true
+            }
+        }
+    }
+
+    async fn receive(&mut self, rx: &mut [RxMessage]) -> bool {
+        match self {
+            Self::SOEM(soem) => Link::receive(soem, rx).await.unwrap_or(false),
+            Self::Mock(cpus) => {
+                rx.iter_mut().zip(cpus.iter()).for_each(|(r, cpu)| {
+                    *r = cpu.rx();
+                });
+                true
+            }
+        }
+    }
+
+    async fn clear(&mut self) -> bool {
+        match self {
+            Self::SOEM(soem) => soem.clear_iomap().await.is_ok(),
+            Self::Mock(cpus) => {
+                let num_dev = cpus.len();
+                *self = Self::mock(num_dev);
+                true
+            }
+        }
+    }
 }
 
 struct SOEMServer {
     num_dev: usize,
-    soem: RwLock<SOEM>,
+    backend: RwLock<Backend>,
+    lost: LostSlaves,
+}
+
+impl SOEMServer {
+    fn check_lost(&self) -> Result<(), Status> {
+        match self.lost.lock().unwrap().values().next() {
+            Some(err) => Err(Status::unavailable(err.clone())),
+            None => Ok(()),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -92,36 +360,96 @@ impl ecat_server::Ecat for SOEMServer {
         &self,
         request: Request<TxRawData>,
     ) -> Result<Response<SendResponse>, Status> {
+        self.check_lost()?;
         let tx = Vec::<TxMessage>::from_msg(&request.into_inner())?;
         Ok(Response::new(SendResponse {
-            success: Link::send(&mut *self.soem.write().await, &tx)
-                .await
-                .unwrap_or(false),
+            success: self.backend.write().await.send(&tx).await,
         }))
     }
 
-    async fn read_data(&self, _: Request<ReadRequest>) -> Result<Response<RxMessage>, Status> {
-        let mut rx = vec![autd3_driver::firmware::cpu::RxMessage::new(0, 0); self.num_dev];
-        Link::receive(&mut *self.soem.write().await, &mut rx)
-            .await
-            .unwrap_or(false);
+    async fn read_data(
+        &self,
+        _: Request<ReadRequest>,
+    ) -> Result<Response<autd3_protobuf::RxMessage>, Status> {
+        self.check_lost()?;
+        let mut rx = vec![RxMessage::new(0, 0); self.num_dev];
+        self.backend.write().await.receive(&mut rx).await;
         Ok(Response::new(rx.to_msg(None)))
     }
 
     async fn close(&self, _: Request<CloseRequest>) -> Result<Response<CloseResponse>, Status> {
-        self.soem
-            .write()
-            .await
-            .clear_iomap()
-            .await
-            .map_err(|_| Status::invalid_argument("Failed to clear data"))?;
+        if !self.backend.write().await.clear().await {
+            return Err(Status::invalid_argument("Failed to clear data"));
+        }
         Ok(Response::new(CloseResponse { success: true }))
     }
 }
 
-async fn main_() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+/// Compressed requests are accepted regardless of `compression`, and the responses are compressed
+/// only if the client accepts the encoding, so that a client interoperates with any setting
+fn ecat_service(
+    server: SOEMServer,
+    compression: CompressionArg,
+) -> ecat_server::EcatServer<SOEMServer> {
+    let service = ecat_server::EcatServer::new(server)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+    match compression.encoding() {
+        Some(encoding) => service.send_compressed(encoding),
+        None => service,
+    }
+}
+
+fn server_builder(args: &Arg) -> Server {
+    let builder = Server::builder()
+        .http2_keepalive_interval(args.keepalive_interval.map(Duration::from_secs))
+        .http2_keepalive_timeout(args.keepalive_timeout.map(Duration::from_secs));
+    match args.concurrency_limit_per_connection {
+        Some(limit) => builder.concurrency_limit_per_connection(limit),
+        None => builder,
+    }
+}
+
+async fn selftest(args: &SelftestArg) -> anyhow::Result<()> {
+    let send_cycle = Duration::from_micros(args.send.get());
+    let sync0_cycle = Duration::from_micros(args.sync0.get());
+
+    tracing::info!("Opening SOEM link...");
+    let start = std::time::Instant::now();
+    let mut soem = autd3_link_soem::SOEM::builder()
+        .with_ifname(args.ifname.clone())
+        .with_send_cycle(send_cycle)
+        .with_sync0_cycle(sync0_cycle)
+        .with_timer_strategy(args.timer_strategy.into())
+        .with_sync_timeout(Duration::from_secs(args.sync_timeout))
+        .open(&autd3_driver::geometry::Geometry::new(vec![], 4))
+        .await?;
+    let open_time = start.elapsed();
+
+    let num_dev = SOEM::num_devices();
+    let mut rx = vec![RxMessage::new(0, 0); num_dev];
+    let start = std::time::Instant::now();
+    let received = Link::receive(&mut soem, &mut rx).await?;
+    let receive_time = start.elapsed();
+
+    Link::close(&mut soem).await?;
+
+    println!("Devices found:\t{}", num_dev);
+    println!("Send cycle:\t{:?}", send_cycle);
+    println!("Sync0 cycle:\t{:?}", sync0_cycle);
+    println!("Open time:\t{:?}", open_time);
+    println!("Receive time:\t{:?}", receive_time);
+
+    if num_dev == 0 {
+        anyhow::bail!("No AUTD found on the link");
+    }
+    if !received {
+        anyhow::bail!("Failed to receive data from the devices");
+    }
+    Ok(())
+}
 
+async fn main_(cli: Cli) -> anyhow::Result<()> {
     match &cli.command {
         Commands::List => {
             println!("Available interfaces:");
@@ -135,6 +463,7 @@ async fn main_() -> anyhow::Result<()> {
                 println!("\t{:name_len$}\t{}", adapter.name(), adapter.desc());
             });
         }
+        Commands::Selftest(args) => selftest(args).await?,
         Commands::Run(args) => {
             let port = args.port;
             let ifname = args.ifname.to_string();
@@ -143,67 +472,95 @@ async fn main_() -> anyhow::Result<()> {
             let state_check_interval = args.state_check_interval;
             let sync_tolerance = std::time::Duration::from_micros(args.sync_tolerance);
             let sync_timeout = std::time::Duration::from_secs(args.sync_timeout);
-            let timer_strategy = match args.timer_strategy {
-                TimerStrategyArg::StdSleep => TimerStrategy::StdSleep,
-                TimerStrategyArg::SpinSleep => TimerStrategy::SpinSleep,
-                TimerStrategyArg::SpinWait => TimerStrategy::SpinWait,
-            };
+            let timer_strategy: TimerStrategy = args.timer_strategy.into();
             let buf_size = args.buf_size;
-            let f = move || -> autd3_link_soem::local::SOEMBuilder {
-                autd3_link_soem::SOEM::builder()
-                    .with_buf_size(buf_size)
-                    .with_ifname(ifname.clone())
-                    .with_send_cycle(std::time::Duration::from_micros(send_cycle.get()))
-                    .with_state_check_interval(std::time::Duration::from_millis(
-                        state_check_interval.get(),
-                    ))
-                    .with_sync0_cycle(std::time::Duration::from_micros(sync0_cycle.get()))
-                    .with_timer_strategy(timer_strategy)
-                    .with_sync_tolerance(sync_tolerance)
-                    .with_sync_timeout(sync_timeout)
-                    .with_err_handler(|slave, status| {
-                        tracing::error!("slave [{}]: {}", slave, status);
-                        if status == autd3_link_soem::Status::Lost {
-                            std::process::exit(-1);
-                        }
-                    })
+            let priority = args.priority;
+            let on_error = args.on_error;
+            let lost = LostSlaves::default();
+            let f = {
+                let lost = lost.clone();
+                move || -> autd3_link_soem::local::SOEMBuilder {
+                    let builder = autd3_link_soem::SOEM::builder()
+                        .with_buf_size(buf_size)
+                        .with_ifname(ifname.clone())
+                        .with_send_cycle(std::time::Duration::from_micros(send_cycle.get()))
+                        .with_state_check_interval(std::time::Duration::from_millis(
+                            state_check_interval.get(),
+                        ))
+                        .with_sync0_cycle(std::time::Duration::from_micros(sync0_cycle.get()))
+                        .with_timer_strategy(timer_strategy)
+                        .with_sync_tolerance(sync_tolerance)
+                        .with_sync_timeout(sync_timeout)
+                        .with_err_handler(err_handler(on_error, lost.clone(), || {
+                            std::process::exit(-1)
+                        }));
+                    match priority {
+                        Some(priority) => builder.with_thread_priority(priority),
+                        None => builder,
+                    }
+                }
             };
-            let (tx, mut rx) = mpsc::channel(1);
-            ctrlc::set_handler(move || {
-                let rt = Runtime::new().expect("failed to obtain a new Runtime object");
-                rt.block_on(tx.send(())).unwrap();
-            })
-            .expect("Error setting Ctrl-C handler");
 
             let addr = format!("0.0.0.0:{}", port).parse()?;
             tracing::info!("Waiting for client connection on {}", addr);
 
+            let connected = Arc::new(AtomicBool::new(false));
+            let on_request = {
+                let connected = connected.clone();
+                move |req: Request<()>| {
+                    connected.store(true, Ordering::Relaxed);
+                    Ok(req)
+                }
+            };
+            let shutdown =
+                shutdown_or_timeout(Duration::from_secs(args.connect_timeout), connected);
+
+            if args.lightweight && args.mock {
+                anyhow::bail!("--mock cannot be used with --lightweight");
+            }
+
             if args.lightweight {
                 let server = autd3_protobuf::lightweight::LightweightServer::new(f);
-                Server::builder()
-                    .add_service(ecat_light_server::EcatLightServer::new(server))
-                    .serve_with_shutdown(addr, async {
-                        let _ = rx.recv().await;
-                    })
+                let mut service = ecat_light_server::EcatLightServer::new(server)
+                    .accept_compressed(CompressionEncoding::Gzip)
+                    .accept_compressed(CompressionEncoding::Zstd);
+                if let Some(encoding) = args.compression.encoding() {
+                    service = service.send_compressed(encoding);
+                }
+                server_builder(args)
+                    .layer(tonic::service::interceptor(on_request))
+                    .add_service(service)
+                    .serve_with_shutdown(addr, shutdown)
                     .await?;
             } else {
-                tracing::info!("Starting SOEM server...");
+                let (num_dev, backend) = if args.mock {
+                    let num_dev = args.mock_devices.get();
+                    tracing::info!("Starting mock server with {} emulated AUTDs...", num_dev);
+                    (num_dev, Backend::mock(num_dev))
+                } else {
+                    tracing::info!("Starting SOEM server...");
 
-                let soem = f()
-                    .open(&autd3_driver::geometry::Geometry::new(vec![], 4))
-                    .await?;
-                let num_dev = SOEM::num_devices();
+                    let soem = f()
+                        .open(&autd3_driver::geometry::Geometry::new(vec![], 4))
+                        .await?;
+                    let num_dev = SOEM::num_devices();
 
-                tracing::info!("{} AUTDs found", num_dev);
+                    tracing::info!("{} AUTDs found", num_dev);
+                    (num_dev, Backend::SOEM(soem))
+                };
 
-                Server::builder()
-                    .add_service(ecat_server::EcatServer::new(SOEMServer {
+                let service = ecat_service(
+                    SOEMServer {
                         num_dev,
-                        soem: RwLock::new(soem),
-                    }))
-                    .serve_with_shutdown(addr, async {
-                        let _ = rx.recv().await;
-                    })
+                        backend: RwLock::new(backend),
+                        lost,
+                    },
+                    args.compression,
+                );
+                server_builder(args)
+                    .layer(tonic::service::interceptor(on_request))
+                    .add_service(service)
+                    .serve_with_shutdown(addr, shutdown)
                     .await?;
             }
         }
@@ -212,11 +569,46 @@ async fn main_() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt().event_format(LogFormatter).init();
+fn init_logger(format: LogFormatArg) {
+    match format {
+        LogFormatArg::Text => tracing_subscriber::fmt().event_format(LogFormatter).init(),
+        LogFormatArg::Json => tracing_subscriber::fmt().json().init(),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logger(cli.log_format);
+
+    // the SOEM link does not expose its threads, so the whole process is pinned
+    // before the runtime is built, and the runtime workers and the threads spawned
+    // by the SOEM link inherit it in both the normal and the lightweight mode
+    if let Commands::Run(Arg {
+        affinity: Some(cpus),
+        ..
+    }) = &cli.command
+    {
+        match set_affinity(cpus) {
+            Ok(()) => tracing::info!("Process affinity is set to {:?}", cpus),
+            Err(e) => {
+                tracing::error!("Failed to set process affinity: {}", e);
+                std::process::exit(-1);
+            }
+        }
+    }
 
-    match main_().await {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(-1);
+        }
+    };
+
+    match runtime.block_on(main_(cli)) {
         Ok(_) => {}
         Err(e) => {
             tracing::error!("{}", e);
@@ -224,3 +616,141 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use autd3_protobuf::ecat_server::Ecat;
+
+    const NUM_DEV: usize = 2;
+    const LOST: usize = 1;
+
+    fn mock_server() -> SOEMServer {
+        SOEMServer {
+            num_dev: NUM_DEV,
+            backend: RwLock::new(Backend::mock(NUM_DEV)),
+            lost: Default::default(),
+        }
+    }
+
+    fn tx() -> Request<TxRawData> {
+        Request::new(TxRawData {
+            data: vec![0; NUM_DEV * std::mem::size_of::<TxMessage>()],
+            n: NUM_DEV as _,
+        })
+    }
+
+    async fn assert_reported(server: &SOEMServer) {
+        let status = server.send_data(tx()).await.unwrap_err();
+        assert_eq!(tonic::Code::Unavailable, status.code());
+        assert_eq!(
+            format!("slave [{}]: {}", LOST, autd3_link_soem::Status::Lost),
+            status.message()
+        );
+        let status = server
+            .read_data(Request::new(ReadRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unavailable, status.code());
+    }
+
+    async fn assert_ok(server: &SOEMServer) {
+        assert!(server.send_data(tx()).await.unwrap().into_inner().success);
+        assert!(server
+            .read_data(Request::new(ReadRequest::default()))
+            .await
+            .is_ok());
+    }
+
+    async fn round_trip(server_compression: CompressionArg, client_compression: CompressionArg) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_th = tokio::spawn(
+            Server::builder()
+                .add_service(ecat_service(mock_server(), server_compression))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = ecat_client::EcatClient::new(channel);
+        if let Some(encoding) = client_compression.encoding() {
+            client = client.send_compressed(encoding).accept_compressed(encoding);
+        }
+        assert!(client.send_data(tx()).await.unwrap().into_inner().success);
+        assert!(client.read_data(ReadRequest::default()).await.is_ok());
+
+        server_th.abort();
+    }
+
+    #[tokio::test]
+    async fn compression() {
+        // a compressed client against an uncompressed server
+        round_trip(CompressionArg::None, CompressionArg::Gzip).await;
+        // an uncompressed client against a compressed server
+        round_trip(CompressionArg::Gzip, CompressionArg::None).await;
+        round_trip(CompressionArg::Gzip, CompressionArg::Gzip).await;
+        round_trip(CompressionArg::Zstd, CompressionArg::Gzip).await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_cpus() {
+        assert!(super::check_cpus(&[0, 2, 3], 4).is_ok());
+        assert!(super::check_cpus(&[0, 4], 4).is_err());
+        assert!(super::check_cpus(&[1024], 1024).is_err());
+    }
+
+    #[test]
+    fn error_policy_exit() {
+        let server = mock_server();
+        let exited = Arc::new(AtomicBool::new(false));
+        let handler = err_handler(ErrorPolicyArg::Exit, server.lost.clone(), {
+            let exited = exited.clone();
+            move || exited.store(true, Ordering::Relaxed)
+        });
+
+        handler(LOST, autd3_link_soem::Status::StateChanged);
+        assert!(!exited.load(Ordering::Relaxed));
+
+        handler(LOST, autd3_link_soem::Status::Lost);
+        assert!(exited.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn error_policy_continue() {
+        let server = mock_server();
+        let handler = err_handler(ErrorPolicyArg::Continue, server.lost.clone(), || {
+            panic!("must not exit")
+        });
+
+        handler(LOST, autd3_link_soem::Status::Lost);
+        assert_ok(&server).await;
+    }
+
+    #[tokio::test]
+    async fn error_policy_report() {
+        let server = mock_server();
+        let handler = err_handler(ErrorPolicyArg::Report, server.lost.clone(), || {
+            panic!("must not exit")
+        });
+
+        assert_ok(&server).await;
+
+        handler(LOST, autd3_link_soem::Status::Lost);
+        assert_reported(&server).await;
+        // the error is reported until the slave is found again
+        assert_reported(&server).await;
+
+        // another slave does not recover the lost one
+        handler(0, autd3_link_soem::Status::StateChanged);
+        assert_reported(&server).await;
+
+        handler(LOST, autd3_link_soem::Status::StateChanged);
+        assert_ok(&server).await;
+    }
+}
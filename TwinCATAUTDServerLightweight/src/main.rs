@@ -2,16 +2,29 @@
 
 mod log_formatter;
 
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
 use log_formatter::LogFormatter;
 
-use autd3_link_twincat::TwinCAT;
+use autd3_link_twincat::{RemoteTwinCAT, TwinCAT};
+use autd3_server_shutdown::shutdown_signal;
 
 use autd3_protobuf::{lightweight::LightweightServer, *};
 
-use tokio::{runtime::Runtime, sync::mpsc};
 use tonic::transport::Server;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum LogFormatArg {
+    /// human readable text
+    Text,
+    /// JSON lines
+    Json,
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -22,40 +35,118 @@ struct Arg {
     /// Client port
     #[clap(short = 'p', long = "port")]
     port: u16,
+    /// Address to listen on
+    #[clap(long = "bind", default_value = "0.0.0.0")]
+    bind: IpAddr,
+    /// AMS net id of the remote TwinCAT server (Optional, if not set, local TwinCAT is used)
+    #[clap(long = "server_ams_net_id", value_parser = parse_ams_net_id)]
+    server_ams_net_id: Option<String>,
+    /// IP address of the remote TwinCAT server (Optional, derived from AMS net id if not set)
+    #[clap(long = "server_ip", requires = "server_ams_net_id")]
+    server_ip: Option<IpAddr>,
+    /// AMS net id of this machine (Optional)
+    #[clap(long = "client_ams_net_id", value_parser = parse_ams_net_id, requires = "server_ams_net_id")]
+    client_ams_net_id: Option<String>,
+    /// HTTP/2 keepalive ping interval in s (Optional, disabled if not set)
+    #[clap(long = "keepalive_interval")]
+    keepalive_interval: Option<u64>,
+    /// Timeout of the HTTP/2 keepalive ping in s (Optional, 20 s if not set)
+    #[clap(long = "keepalive_timeout")]
+    keepalive_timeout: Option<u64>,
+    /// Maximum number of requests processed at once on each client connection; the number of connections is not limited (Optional, unlimited if not set)
+    #[clap(long = "concurrency_limit_per_connection")]
+    concurrency_limit_per_connection: Option<usize>,
+    /// Log output format
+    #[clap(long = "log_format", default_value = "text")]
+    log_format: LogFormatArg,
 }
 
-async fn main_() -> anyhow::Result<()> {
-    let arg = Arg::parse();
-
-    let port = arg.port;
-
-    let server = LightweightServer::new(TwinCAT::builder);
-
-    let (tx, mut rx) = mpsc::channel(1);
-    ctrlc::set_handler(move || {
-        let rt = Runtime::new().expect("failed to obtain a new Runtime object");
-        rt.block_on(tx.send(())).unwrap();
-    })
-    .expect("Error setting Ctrl-C handler");
+fn parse_ams_net_id(s: &str) -> Result<String, String> {
+    let parts = s.split('.').collect::<Vec<_>>();
+    if parts.len() != 6 || parts.iter().any(|p| p.parse::<u8>().is_err()) {
+        return Err(format!(
+            "invalid AMS net id: {} (expected six dot-separated bytes, e.g. 172.16.99.1.1.1)",
+            s
+        ));
+    }
+    Ok(s.to_string())
+}
 
-    let addr = format!("0.0.0.0:{}", port).parse()?;
-    tracing::info!("Waiting for client connection on {}", addr);
+fn server_builder(arg: &Arg) -> Server {
+    let builder = Server::builder()
+        .http2_keepalive_interval(arg.keepalive_interval.map(Duration::from_secs))
+        .http2_keepalive_timeout(arg.keepalive_timeout.map(Duration::from_secs));
+    match arg.concurrency_limit_per_connection {
+        Some(limit) => builder.concurrency_limit_per_connection(limit),
+        None => builder,
+    }
+}
 
-    Server::builder()
+async fn serve<T: ecat_light_server::EcatLight>(
+    mut builder: Server,
+    server: T,
+    addr: SocketAddr,
+) -> anyhow::Result<()> {
+    builder
         .add_service(ecat_light_server::EcatLightServer::new(server))
-        .serve_with_shutdown(addr, async {
-            let _ = rx.recv().await;
-        })
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
-
     Ok(())
 }
 
+async fn main_(arg: Arg) -> anyhow::Result<()> {
+    let port = arg.port;
+
+    let addr = SocketAddr::new(arg.bind, port);
+    let builder = server_builder(&arg);
+
+    match arg.server_ams_net_id {
+        Some(server_ams_net_id) => {
+            tracing::info!(
+                "Using remote TwinCAT: server AMS net id: {}, server ip: {}, client AMS net id: {}",
+                server_ams_net_id,
+                arg.server_ip
+                    .map_or("(default)".to_string(), |ip| ip.to_string()),
+                arg.client_ams_net_id.as_deref().unwrap_or("(default)")
+            );
+            let server_ip = arg.server_ip;
+            let client_ams_net_id = arg.client_ams_net_id;
+            let server = LightweightServer::new(move || {
+                let builder = RemoteTwinCAT::builder(server_ams_net_id.clone());
+                let builder = match server_ip {
+                    Some(ip) => builder.with_server_ip(ip.to_string()),
+                    None => builder,
+                };
+                match &client_ams_net_id {
+                    Some(id) => builder.with_client_ams_net_id(id.clone()),
+                    None => builder,
+                }
+            });
+            tracing::info!("Waiting for client connection on {}", addr);
+            serve(builder, server, addr).await
+        }
+        None => {
+            tracing::info!("Using local TwinCAT");
+            let server = LightweightServer::new(TwinCAT::builder);
+            tracing::info!("Waiting for client connection on {}", addr);
+            serve(builder, server, addr).await
+        }
+    }
+}
+
+fn init_logger(format: LogFormatArg) {
+    match format {
+        LogFormatArg::Text => tracing_subscriber::fmt().event_format(LogFormatter).init(),
+        LogFormatArg::Json => tracing_subscriber::fmt().json().init(),
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt().event_format(LogFormatter).init();
+    let arg = Arg::parse();
+    init_logger(arg.log_format);
 
-    match main_().await {
+    match main_(arg).await {
         Ok(_) => {}
         Err(e) => {
             tracing::error!("{}", e);
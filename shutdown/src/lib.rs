@@ -0,0 +1,97 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Resolves when Ctrl+C, or Ctrl-Break on Windows, is received.
+pub async fn shutdown_signal() {
+    #[cfg(windows)]
+    {
+        let mut ctrl_break =
+            tokio::signal::windows::ctrl_break().expect("Error setting Ctrl-Break handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = ctrl_break.recv() => {}
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Resolves on [`shutdown_signal`], or when no client has connected within `timeout`.
+///
+/// A zero `timeout` waits for the signal only.
+pub async fn shutdown_or_timeout(timeout: Duration, connected: Arc<AtomicBool>) {
+    signal_or_timeout(shutdown_signal(), timeout, connected).await
+}
+
+async fn signal_or_timeout(
+    signal: impl Future<Output = ()>,
+    timeout: Duration,
+    connected: Arc<AtomicBool>,
+) {
+    let timeout = async {
+        if timeout.is_zero() {
+            return std::future::pending().await;
+        }
+        tokio::time::sleep(timeout).await;
+        if connected.load(Ordering::Relaxed) {
+            return std::future::pending().await;
+        }
+        tracing::warn!(
+            "No client connected within {:?}, shutting down the server",
+            timeout
+        );
+    };
+    tokio::select! {
+        _ = signal => {}
+        _ = timeout => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    async fn resolves(
+        signal: impl Future<Output = ()>,
+        timeout: Duration,
+        connected: bool,
+    ) -> bool {
+        tokio::time::timeout(
+            TIMEOUT * 2,
+            signal_or_timeout(signal, timeout, Arc::new(AtomicBool::new(connected))),
+        )
+        .await
+        .is_ok()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn signal_resolves() {
+        assert!(resolves(std::future::ready(()), Duration::ZERO, false).await);
+        assert!(resolves(std::future::ready(()), TIMEOUT, true).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_resolves_without_client() {
+        assert!(resolves(std::future::pending(), TIMEOUT, false).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_waits_for_signal_with_client() {
+        assert!(!resolves(std::future::pending(), TIMEOUT, true).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_timeout_waits_for_signal() {
+        assert!(!resolves(std::future::pending(), Duration::ZERO, false).await);
+    }
+}
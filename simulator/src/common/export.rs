@@ -0,0 +1,27 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use autd3_driver::derive::Segment;
+
+pub fn write_modulation_csv(
+    path: &Path,
+    segment: Segment,
+    freq_division: impl std::fmt::Display,
+    buffer: &[u8],
+) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "# segment: {:?}", segment)?;
+    writeln!(writer, "# freq_division: {}", freq_division)?;
+    writeln!(writer, "index,value")?;
+    buffer
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, v)| writeln!(writer, "{},{}", i, v))?;
+    writer.flush()
+}
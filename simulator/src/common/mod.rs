@@ -1,4 +1,7 @@
 pub mod camera;
 pub mod color;
 pub mod color_map;
+pub mod export;
+pub mod picking;
 pub mod transform;
+pub mod unit;
@@ -0,0 +1,110 @@
+use crate::{
+    common::transform::{to_gl_pos, to_gl_rot},
+    state::SliceState,
+    Matrix4, Vector2, Vector3, Vector4,
+};
+
+pub struct Ray {
+    pub origin: Vector3,
+    pub dir: Vector3,
+}
+
+impl Ray {
+    pub fn from_screen(view_proj: Matrix4, pos: Vector2, size: Vector2) -> Self {
+        let ndc = Vector2::new(2. * pos.x / size.x - 1., 1. - 2. * pos.y / size.y);
+        let inv = view_proj.inverse();
+        let near = inv.project_point3(Vector3::new(ndc.x, ndc.y, 0.));
+        let far = inv.project_point3(Vector3::new(ndc.x, ndc.y, 1.));
+        Self {
+            origin: near,
+            dir: (far - near).normalize(),
+        }
+    }
+
+    pub fn intersect_slice(&self, slice: &SliceState) -> Option<Vector3> {
+        let center = to_gl_pos(slice.pos);
+        let rot = to_gl_rot(slice.rotation());
+        let normal = rot * Vector3::Z;
+
+        let denom = self.dir.dot(normal);
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = (center - self.origin).dot(normal) / denom;
+        if t < 0. {
+            return None;
+        }
+
+        let p = self.origin + self.dir * t;
+        let local = rot.inverse() * (p - center);
+        if local.x.abs() > slice.size.x / 2. || local.y.abs() > slice.size.y / 2. {
+            return None;
+        }
+        Some(to_gl_pos(p))
+    }
+
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let inv = self.dir.recip();
+        let t0 = (aabb.min - self.origin) * inv;
+        let t1 = (aabb.max - self.origin) * inv;
+        let t_near = t0.min(t1).max_element();
+        let t_far = t0.max(t1).min_element();
+        (t_near <= t_far && t_far >= 0.).then_some(t_near.max(0.))
+    }
+}
+
+/// Axis-aligned bounding box in GL coordinates
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn from_points(points: impl IntoIterator<Item = Vector3>, margin: f32) -> Self {
+        let (min, max) = points.into_iter().fold(
+            (Vector3::splat(f32::MAX), Vector3::splat(f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+        Self {
+            min: min - margin,
+            max: max + margin,
+        }
+    }
+
+    pub fn edges(&self) -> [(Vector3, Vector3); 12] {
+        let corner = |i: usize| {
+            Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        };
+        [
+            (0, 1),
+            (2, 3),
+            (4, 5),
+            (6, 7),
+            (0, 2),
+            (1, 3),
+            (4, 6),
+            (5, 7),
+            (0, 4),
+            (1, 5),
+            (2, 6),
+            (3, 7),
+        ]
+        .map(|(a, b)| (corner(a), corner(b)))
+    }
+}
+
+pub fn to_screen(view_proj: Matrix4, p: Vector3, size: Vector2) -> Option<Vector2> {
+    let clip = view_proj * Vector4::from((to_gl_pos(p), 1.));
+    if clip.w <= 0. {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+    Some(Vector2::new(
+        (ndc.x + 1.) / 2. * size.x,
+        (1. - ndc.y) / 2. * size.y,
+    ))
+}
@@ -1,7 +1,19 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use crate::{Quaternion, Vector3};
 
+static LEFT_HANDED: AtomicBool = AtomicBool::new(cfg!(feature = "left_handed"));
+
+pub fn left_handed() -> bool {
+    LEFT_HANDED.load(Ordering::Relaxed)
+}
+
+pub fn set_left_handed(left_handed: bool) {
+    LEFT_HANDED.store(left_handed, Ordering::Relaxed);
+}
+
 pub fn to_gl_pos(v: Vector3) -> Vector3 {
-    if cfg!(feature = "left_handed") {
+    if left_handed() {
         Vector3::new(v.x, v.y, -v.z)
     } else {
         v
@@ -9,7 +21,7 @@ pub fn to_gl_pos(v: Vector3) -> Vector3 {
 }
 
 pub fn to_gl_rot(v: Quaternion) -> Quaternion {
-    if cfg!(feature = "left_handed") {
+    if left_handed() {
         Quaternion::from_xyzw(-v.x, -v.y, v.z, v.w)
     } else {
         v
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+pub fn format_freq(hz: f32, raw: bool) -> String {
+    if raw || hz < 1e3 {
+        format!("{:.3}Hz", hz)
+    } else {
+        format!("{:.3}kHz", hz / 1e3)
+    }
+}
+
+pub fn format_duration(d: Duration, raw: bool) -> String {
+    if raw {
+        return format!("{:?}", d);
+    }
+    let secs = d.as_secs_f64();
+    if secs >= 1. {
+        format!("{:.3}s", secs)
+    } else if secs >= 1e-3 {
+        format!("{:.3}ms", secs * 1e3)
+    } else {
+        format!("{:.3}µs", secs * 1e6)
+    }
+}
@@ -1,6 +1,10 @@
+mod pwm;
 mod transducers;
 
-use std::{f32::consts::PI, sync::Arc};
+pub use pwm::pwm_out;
+pub use transducers::TransState;
+
+use std::{collections::HashMap, f32::consts::PI, ops::Range, sync::Arc};
 
 use autd3_driver::{
     defined::ULTRASOUND_PERIOD_COUNT,
@@ -10,32 +14,68 @@ use autd3_driver::{
 };
 use autd3_firmware_emulator::CPUEmulator;
 use parking_lot::RwLock;
+use serde::Serialize;
+
+use crate::{event::SessionId, state::DeviceState};
+
+pub type RxBuffer = Arc<RwLock<HashMap<SessionId, Vec<RxMessage>>>>;
+pub type DeviceOutputs = Arc<RwLock<Vec<DeviceOutput>>>;
+
+/// What a device is currently outputting, shared with the health endpoint
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct DeviceOutput {
+    pub mod_segment: u8,
+    pub mod_idx: usize,
+    pub stm_segment: u8,
+    pub stm_idx: usize,
+}
+
+impl DeviceOutput {
+    fn new(cpu: &CPUEmulator) -> Self {
+        Self {
+            mod_segment: cpu.fpga().current_mod_segment() as u8,
+            mod_idx: usize::from(cpu.fpga().current_mod_idx()),
+            stm_segment: cpu.fpga().current_stm_segment() as u8,
+            stm_idx: usize::from(cpu.fpga().current_stm_idx()),
+        }
+    }
+}
 
 pub struct Emulator<'a> {
     pub cpu: &'a mut CPUEmulator,
     pub transducers: &'a mut [transducers::TransState],
     pub visible: &'a mut bool,
+    pub alpha: &'a mut f32,
     pub enable: &'a mut bool,
+    pub mod_enable: &'a mut bool,
     pub thermal: &'a mut bool,
 }
 
 pub struct EmulatorWrapper {
     cpus: Vec<CPUEmulator>,
     transducers: transducers::Transducers,
-    rx_buf: Arc<RwLock<Vec<RxMessage>>>,
+    rx_buf: RxBuffer,
+    outputs: DeviceOutputs,
+    sessions: Vec<(SessionId, Range<usize>)>,
     visible: Vec<bool>,
+    alpha: Vec<f32>,
     enable: Vec<bool>,
+    mod_enable: Vec<bool>,
     thermal: Vec<bool>,
 }
 
 impl EmulatorWrapper {
-    pub fn new(rx_buf: Arc<RwLock<Vec<RxMessage>>>) -> Self {
+    pub fn new(rx_buf: RxBuffer, outputs: DeviceOutputs) -> Self {
         Self {
             cpus: Default::default(),
             transducers: transducers::Transducers::new(),
             rx_buf,
+            outputs,
+            sessions: Default::default(),
             visible: Default::default(),
+            alpha: Default::default(),
             enable: Default::default(),
+            mod_enable: Default::default(),
             thermal: Default::default(),
         }
     }
@@ -44,6 +84,28 @@ impl EmulatorWrapper {
         !self.cpus.is_empty()
     }
 
+    pub fn num_devices(&self) -> usize {
+        self.cpus.len()
+    }
+
+    pub fn num_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    fn session_range(&self, session: SessionId) -> Option<Range<usize>> {
+        self.sessions
+            .iter()
+            .find(|(id, _)| *id == session)
+            .map(|(_, range)| range.clone())
+    }
+
+    fn update_rx(&self, session: SessionId, range: Range<usize>) {
+        self.rx_buf.write().insert(
+            session,
+            self.cpus[range].iter().map(|cpu| cpu.rx()).collect(),
+        );
+    }
+
     pub fn transducers(&self) -> &transducers::Transducers {
         &self.transducers
     }
@@ -52,20 +114,30 @@ impl EmulatorWrapper {
         self.cpus
             .iter_mut()
             .zip(self.visible.iter_mut())
+            .zip(self.alpha.iter_mut())
             .zip(self.enable.iter_mut())
+            .zip(self.mod_enable.iter_mut())
             .zip(self.thermal.iter_mut())
             .zip(self.transducers.devices())
             .map(
-                |((((cpu, visible), enable), thermal), transducers)| Emulator {
-                    cpu,
-                    transducers,
-                    visible,
-                    enable,
-                    thermal,
+                |((((((cpu, visible), alpha), enable), mod_enable), thermal), transducers)| {
+                    Emulator {
+                        cpu,
+                        transducers,
+                        visible,
+                        alpha,
+                        enable,
+                        mod_enable,
+                        thermal,
+                    }
                 },
             )
     }
 
+    pub fn flip_handedness(&mut self) {
+        self.transducers.flip_handedness();
+    }
+
     pub fn update(&mut self, system_time: DcSysTime) {
         self.cpus.iter_mut().for_each(|cpu| {
             cpu.update_with_sys_time(system_time);
@@ -75,14 +147,11 @@ impl EmulatorWrapper {
             .iter()
             .any(autd3_firmware_emulator::CPUEmulator::should_update)
         {
-            self.rx_buf
-                .write()
-                .iter_mut()
-                .zip(self.cpus.iter())
-                .for_each(|(d, s)| {
-                    *d = s.rx();
-                });
+            self.sessions
+                .iter()
+                .for_each(|(session, range)| self.update_rx(*session, range.clone()));
         }
+        *self.outputs.write() = self.cpus.iter().map(DeviceOutput::new).collect();
     }
 
     pub fn update_transducers(&mut self, mod_enable: bool) {
@@ -96,7 +165,7 @@ impl EmulatorWrapper {
             };
             let drives = cpu.fpga().drives_at(stm_segment, idx);
             let mod_segment = cpu.fpga().current_mod_segment();
-            let m = if mod_enable {
+            let m = if mod_enable && *emulator.mod_enable {
                 let mod_idx = cpu.fpga().current_mod_idx();
                 cpu.fpga().modulation_at(mod_segment, mod_idx)
             } else {
@@ -115,40 +184,311 @@ impl EmulatorWrapper {
         });
     }
 
-    pub fn initialize(&mut self, geometry: &Geometry) {
-        self.cpus = geometry
+    pub fn device_states(&self) -> Vec<DeviceState> {
+        self.visible
             .iter()
-            .map(|dev| CPUEmulator::new(dev.idx(), dev.num_transducers()))
-            .collect();
-        self.transducers.initialize(geometry);
-        *self.rx_buf.write() = self.cpus.iter().map(|cpu| cpu.rx()).collect();
-        self.visible = vec![true; self.cpus.len()];
-        self.enable = vec![true; self.cpus.len()];
-        self.thermal = vec![false; self.cpus.len()];
+            .zip(self.alpha.iter())
+            .zip(self.enable.iter())
+            .zip(self.mod_enable.iter())
+            .zip(self.thermal.iter())
+            .map(
+                |((((&visible, &alpha), &enable), &mod_enable), &thermal)| DeviceState {
+                    visible,
+                    alpha,
+                    enable,
+                    mod_enable,
+                    thermal,
+                },
+            )
+            .collect()
+    }
+
+    pub fn initialize(&mut self, session: SessionId, geometry: &Geometry, devices: &[DeviceState]) {
+        self.remove(session);
+
+        let start = self.cpus.len();
+        self.cpus.extend(
+            geometry
+                .iter()
+                .map(|dev| CPUEmulator::new(dev.idx(), dev.num_transducers())),
+        );
+        let range = start..self.cpus.len();
+        self.transducers.append(geometry);
+        self.visible.resize(range.end, true);
+        self.alpha.resize(range.end, 1.);
+        self.enable.resize(range.end, true);
+        self.mod_enable.resize(range.end, true);
+        self.thermal.resize(range.end, false);
+        self.update_rx(session, range.clone());
+        self.sessions.push((session, range.clone()));
+
+        if devices.len() == self.cpus.len() {
+            self.iter_mut()
+                .zip(devices.iter())
+                .skip(range.start)
+                .for_each(|(emulator, state)| Self::apply_device_state(emulator, state));
+        }
     }
 
-    pub fn update_geometry(&mut self, geometry: &Geometry) {
-        self.transducers.update_geometry(geometry);
+    /// Applies the UI state of all devices, e.g., after the settings file is reloaded.
+    /// Returns `false` and does nothing if the number of devices does not match.
+    pub fn set_device_states(&mut self, devices: &[DeviceState]) -> bool {
+        if devices.len() != self.cpus.len() {
+            return false;
+        }
+        self.iter_mut()
+            .zip(devices.iter())
+            .for_each(|(emulator, state)| Self::apply_device_state(emulator, state));
+        true
     }
 
-    pub fn send(&mut self, tx: &[TxMessage]) {
-        self.cpus.iter_mut().for_each(|cpu| {
-            cpu.send(tx);
+    fn apply_device_state(emulator: Emulator, state: &DeviceState) {
+        *emulator.visible = state.visible;
+        *emulator.alpha = state.alpha;
+        *emulator.enable = state.enable;
+        *emulator.mod_enable = state.mod_enable;
+        *emulator.thermal = state.thermal;
+        let alpha = state.effective_alpha();
+        let enable = if state.enable { 1. } else { 0. };
+        emulator.transducers.iter_mut().for_each(|s| {
+            s.alpha = alpha;
+            s.enable = enable;
         });
-        self.rx_buf
-            .write()
-            .iter_mut()
-            .zip(self.cpus.iter())
-            .for_each(|(d, s)| {
-                *d = s.rx();
+        if state.thermal {
+            emulator.cpu.fpga_mut().assert_thermal_sensor();
+        } else {
+            emulator.cpu.fpga_mut().deassert_thermal_sensor();
+        }
+    }
+
+    /// Removes the devices of the session. Returns `false` if the session is unknown.
+    pub fn remove(&mut self, session: SessionId) -> bool {
+        let Some(range) = self.session_range(session) else {
+            return false;
+        };
+        self.cpus.drain(range.clone());
+        self.transducers.remove_devices(range.clone());
+        self.visible.drain(range.clone());
+        self.alpha.drain(range.clone());
+        self.enable.drain(range.clone());
+        self.mod_enable.drain(range.clone());
+        self.thermal.drain(range.clone());
+        self.rx_buf.write().remove(&session);
+        self.sessions.retain(|(id, _)| *id != session);
+        self.sessions.iter_mut().for_each(|(_, r)| {
+            if r.start >= range.end {
+                *r = r.start - range.len()..r.end - range.len();
+            }
+        });
+        true
+    }
+
+    /// Updates the poses of the devices of the session.
+    /// If the number of devices or transducers has changed, the devices are reallocated, keeping the emulators and UI state of the devices that remain, and `true` is returned.
+    pub fn update_geometry(&mut self, session: SessionId, geometry: &Geometry) -> bool {
+        let Some(range) = self.session_range(session) else {
+            return false;
+        };
+        let num_transducers = self
+            .transducers
+            .device_ranges()
+            .skip(range.start)
+            .take(range.len())
+            .map(|r| r.len())
+            .collect::<Vec<_>>();
+        if num_transducers.len() == geometry.num_devices()
+            && num_transducers
+                .iter()
+                .zip(geometry.iter())
+                .all(|(&n, dev)| n == dev.num_transducers())
+        {
+            self.transducers.update_geometry(range.start, geometry);
+            return false;
+        }
+
+        let mut old_cpus = self
+            .cpus
+            .drain(range.clone())
+            .zip(num_transducers)
+            .collect::<Vec<_>>()
+            .into_iter();
+        let cpus = geometry
+            .iter()
+            .map(|dev| match old_cpus.next() {
+                Some((cpu, n)) if n == dev.num_transducers() => cpu,
+                _ => CPUEmulator::new(dev.idx(), dev.num_transducers()),
+            })
+            .collect::<Vec<_>>();
+        let new_range = range.start..range.start + cpus.len();
+        self.cpus.splice(range.start..range.start, cpus);
+        self.transducers.replace_devices(range.clone(), geometry);
+        Self::resize_range(&mut self.visible, &range, new_range.len(), true);
+        Self::resize_range(&mut self.alpha, &range, new_range.len(), 1.);
+        Self::resize_range(&mut self.enable, &range, new_range.len(), true);
+        Self::resize_range(&mut self.mod_enable, &range, new_range.len(), true);
+        Self::resize_range(&mut self.thermal, &range, new_range.len(), false);
+        self.sessions.iter_mut().for_each(|(id, r)| {
+            if *id == session {
+                *r = new_range.clone();
+            } else if r.start >= range.end {
+                *r = r.start - range.len() + new_range.len()..r.end - range.len() + new_range.len();
+            }
+        });
+
+        self.iter_mut()
+            .skip(new_range.start)
+            .take(new_range.len())
+            .for_each(|emulator| {
+                let alpha = if *emulator.visible {
+                    *emulator.alpha
+                } else {
+                    0.
+                };
+                let enable = if *emulator.enable { 1. } else { 0. };
+                emulator.transducers.iter_mut().for_each(|s| {
+                    s.alpha = alpha;
+                    s.enable = enable;
+                });
             });
+        self.update_rx(session, new_range);
+        true
+    }
+
+    fn resize_range<T: Clone>(v: &mut Vec<T>, range: &Range<usize>, len: usize, value: T) {
+        if len < range.len() {
+            v.drain(range.start + len..range.end);
+        } else {
+            v.splice(
+                range.end..range.end,
+                std::iter::repeat(value).take(len - range.len()),
+            );
+        }
+    }
+
+    pub fn send(&mut self, session: SessionId, tx: &[TxMessage]) {
+        let Some(range) = self.session_range(session) else {
+            return;
+        };
+        self.cpus[range.clone()].iter_mut().for_each(|cpu| {
+            cpu.send(tx);
+        });
+        self.update_rx(session, range);
     }
 
     pub fn clear(&mut self) {
         self.cpus.clear();
         self.transducers.clear();
+        self.rx_buf.write().clear();
+        self.sessions.clear();
         self.visible.clear();
+        self.alpha.clear();
         self.enable.clear();
+        self.mod_enable.clear();
         self.thermal.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use autd3_driver::autd3_device::AUTD3;
+
+    use super::*;
+    use crate::geometry_file::grid_geometry;
+
+    fn session(port: u16) -> SessionId {
+        Some(std::net::SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    fn hidden() -> DeviceState {
+        DeviceState {
+            visible: false,
+            alpha: 0.5,
+            ..Default::default()
+        }
+    }
+
+    fn emulator(sessions: &[(SessionId, usize)]) -> EmulatorWrapper {
+        let mut emulator = EmulatorWrapper::new(Default::default(), Default::default());
+        sessions.iter().for_each(|&(session, num_devices)| {
+            emulator.initialize(session, &grid_geometry(num_devices, num_devices), &[]);
+        });
+        emulator.iter_mut().for_each(|emulator| {
+            *emulator.visible = false;
+            *emulator.alpha = 0.5;
+        });
+        emulator
+    }
+
+    fn assert_devices(emulator: &EmulatorWrapper, expected: &[DeviceState]) {
+        assert_eq!(expected.len(), emulator.num_devices());
+        assert_eq!(
+            expected.len() * AUTD3::NUM_TRANS_IN_UNIT,
+            emulator.transducers().len()
+        );
+        assert_eq!(expected, emulator.device_states());
+    }
+
+    #[test]
+    fn update_geometry_pose_only() {
+        let mut emulator = emulator(&[(None, 2)]);
+        assert!(!emulator.update_geometry(None, &grid_geometry(2, 1)));
+        assert_devices(&emulator, &[hidden(), hidden()]);
+    }
+
+    #[test]
+    fn update_geometry_grow() {
+        let mut emulator = emulator(&[(session(1), 2), (session(2), 1)]);
+        assert!(emulator.update_geometry(session(1), &grid_geometry(3, 3)));
+        assert_devices(
+            &emulator,
+            &[hidden(), hidden(), DeviceState::default(), hidden()],
+        );
+        assert_eq!(Some(0..3), emulator.session_range(session(1)));
+        assert_eq!(Some(3..4), emulator.session_range(session(2)));
+        assert_eq!(3, emulator.rx_buf.read()[&session(1)].len());
+    }
+
+    #[test]
+    fn update_geometry_shrink() {
+        let mut emulator = emulator(&[(session(1), 3), (session(2), 1)]);
+        assert!(emulator.update_geometry(session(1), &grid_geometry(1, 1)));
+        assert_devices(&emulator, &[hidden(), hidden()]);
+        assert_eq!(Some(0..1), emulator.session_range(session(1)));
+        assert_eq!(Some(1..2), emulator.session_range(session(2)));
+        assert_eq!(1, emulator.rx_buf.read()[&session(1)].len());
+    }
+
+    #[test]
+    fn set_device_states() {
+        let mut emulator = emulator(&[(None, 2)]);
+        assert!(!emulator.set_device_states(&[DeviceState::default()]));
+        assert_devices(&emulator, &[hidden(), hidden()]);
+
+        let devices = [
+            DeviceState {
+                enable: false,
+                thermal: true,
+                ..Default::default()
+            },
+            hidden(),
+        ];
+        assert!(emulator.set_device_states(&devices));
+        assert_devices(&emulator, &devices);
+        let states = emulator.transducers().states();
+        assert_eq!((1., 0.), (states[0].alpha, states[0].enable));
+        assert_eq!(
+            (0., 1.),
+            (
+                states[AUTD3::NUM_TRANS_IN_UNIT].alpha,
+                states[AUTD3::NUM_TRANS_IN_UNIT].enable
+            )
+        );
+    }
+
+    #[test]
+    fn update_geometry_unknown_session() {
+        let mut emulator = emulator(&[(session(1), 2)]);
+        assert!(!emulator.update_geometry(session(2), &grid_geometry(3, 3)));
+        assert_devices(&emulator, &[hidden(), hidden()]);
+    }
+}
@@ -0,0 +1,70 @@
+use autd3_driver::defined::ULTRASOUND_PERIOD_COUNT;
+
+const T: u32 = ULTRASOUND_PERIOD_COUNT as u32;
+
+pub fn pwm_out(phase: u32, pulse_width: u32) -> Vec<f32> {
+    let rise = (phase + T - pulse_width / 2) % T;
+    let fall = (phase + (pulse_width + 1) / 2) % T;
+    (0..T)
+        .map(|t| {
+            let high = if rise <= fall {
+                (rise <= t) && (t < fall)
+            } else {
+                (t < fall) || (rise <= t)
+            };
+            if high {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expect(high: &[(u32, u32)]) -> Vec<f32> {
+        (0..T)
+            .map(|t| {
+                if high.iter().any(|&(start, end)| (start..end).contains(&t)) {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pwm_out_table() {
+        [
+            // zero pulse width
+            (0, 0, vec![]),
+            (T / 2, 0, vec![]),
+            (T - 1, 0, vec![]),
+            // minimum pulse width
+            (T / 2, 1, vec![(T / 2, T / 2 + 1)]),
+            // maximum pulse width (50% duty)
+            (T / 2, T / 2, vec![(T / 4, T * 3 / 4)]),
+            (T / 4, T / 2, vec![(0, T / 2)]),
+            // widest pulse
+            (T / 2, T - 1, vec![(1, T)]),
+            // pulses wrapping around the period
+            (0, T / 2, vec![(0, T / 4), (T * 3 / 4, T)]),
+            (T - 1, T / 2, vec![(0, T / 4 - 1), (T * 3 / 4 - 1, T)]),
+            (10, 100, vec![(0, 60), (T - 40, T)]),
+        ]
+        .into_iter()
+        .for_each(|(phase, pulse_width, high)| {
+            assert_eq!(
+                expect(&high),
+                pwm_out(phase, pulse_width),
+                "phase: {}, pulse_width: {}",
+                phase,
+                pulse_width
+            );
+        });
+    }
+}
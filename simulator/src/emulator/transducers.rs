@@ -56,6 +56,10 @@ impl Transducers {
         self.body_pointer.clear();
     }
 
+    pub fn device_ranges(&self) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+        self.body_pointer.windows(2).map(|w| w[0]..w[1])
+    }
+
     pub fn devices(&mut self) -> impl Iterator<Item = &mut [TransState]> {
         unsafe {
             let ptr = self.states.as_mut_ptr();
@@ -65,14 +69,11 @@ impl Transducers {
         }
     }
 
-    pub fn initialize(&mut self, geometry: &Geometry) {
-        self.positions.clear();
-        self.rotations.clear();
-        self.states.clear();
-        self.body_pointer.clear();
-
-        let mut body_cursor = 0;
-        self.body_pointer.push(body_cursor);
+    pub fn append(&mut self, geometry: &Geometry) {
+        if self.body_pointer.is_empty() {
+            self.body_pointer.push(0);
+        }
+        let mut body_cursor = self.positions.len();
         geometry.iter().for_each(|dev| {
             body_cursor += dev.num_transducers();
             self.body_pointer.push(body_cursor);
@@ -97,8 +98,47 @@ impl Transducers {
         });
     }
 
-    pub fn update_geometry(&mut self, geometry: &Geometry) {
-        let mut cursor = 0;
+    pub fn remove_devices(&mut self, devices: std::ops::Range<usize>) {
+        let start = self.body_pointer[devices.start];
+        let end = self.body_pointer[devices.end];
+        self.positions.drain(start..end);
+        self.rotations.drain(start..end);
+        self.states.drain(start..end);
+        self.body_pointer.drain(devices.start + 1..devices.end + 1);
+        self.body_pointer[devices.start + 1..]
+            .iter_mut()
+            .for_each(|p| *p -= end - start);
+    }
+
+    /// Replace the devices in `devices` with the devices of `geometry`, which may have a different number of devices
+    pub fn replace_devices(&mut self, devices: std::ops::Range<usize>, geometry: &Geometry) {
+        let mut replaced = Self::new();
+        replaced.append(geometry);
+        let num_devices = replaced.body_pointer.len() - 1;
+        let start = self.body_pointer[devices.start];
+        let end = self.body_pointer[devices.end];
+        let len = replaced.len();
+        self.positions.splice(start..end, replaced.positions);
+        self.rotations.splice(start..end, replaced.rotations);
+        self.states.splice(start..end, replaced.states);
+        self.body_pointer.splice(
+            devices.start + 1..devices.end + 1,
+            replaced.body_pointer.into_iter().skip(1).map(|p| p + start),
+        );
+        self.body_pointer[devices.start + 1 + num_devices..]
+            .iter_mut()
+            .for_each(|p| *p = *p - (end - start) + len);
+    }
+
+    pub fn flip_handedness(&mut self) {
+        self.positions.iter_mut().for_each(|p| p.z = -p.z);
+        self.rotations
+            .iter_mut()
+            .for_each(|r| *r = Quaternion::from_xyzw(-r.x, -r.y, r.z, r.w));
+    }
+
+    pub fn update_geometry(&mut self, first_device: usize, geometry: &Geometry) {
+        let mut cursor = self.body_pointer[first_device];
         geometry.into_iter().for_each(|dev| {
             let rot = to_gl_rot(Quaternion::from_xyzw(
                 dev.rotation().i,
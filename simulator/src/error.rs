@@ -15,15 +15,29 @@ pub enum SimulatorError {
     #[error("{0}")]
     SurfaceError(#[from] wgpu::SurfaceError),
     #[error("{0}")]
+    BufferAsyncError(#[from] wgpu::BufferAsyncError),
+    #[error("{0}")]
     ImageError(#[from] image::ImageError),
     #[error("{0}")]
+    PngEncodingError(#[from] png::EncodingError),
+    #[error("{0}")]
     AUTDProtoBufError(#[from] autd3_protobuf::AUTDProtoBufError),
     #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("{0}")]
+    DecodeError(#[from] prost::DecodeError),
+    #[error("{0}")]
     IoError(#[from] std::io::Error),
     #[error("{0}")]
     TransportError(#[from] tonic::transport::Error),
     #[error("{0}")]
     JoinError(#[from] tokio::task::JoinError),
+    #[error("Unix domain socket is not supported on this platform")]
+    UdsNotSupported,
+    #[error("Lightweight mode cannot be used with Unix domain socket")]
+    UdsWithLightweight,
+    #[error("{0} already exists and is not a socket")]
+    UdsPathNotSocket(std::path::PathBuf),
     #[error("Failed to find adapter")]
     NoSuitableAdapter,
     #[error("Failed to select proper surface texture format")]
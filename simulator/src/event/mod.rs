@@ -1,8 +1,12 @@
 mod signal;
 
-use std::time::Instant;
+use std::{sync::mpsc::Sender, time::Instant};
 
-pub use signal::Signal;
+pub use signal::{SessionId, Signal};
+use tokio::sync::oneshot;
+use winit::event_loop::{EventLoopClosed, EventLoopProxy};
+
+use crate::Vector3;
 
 #[derive(Debug)]
 pub enum UserEvent {
@@ -10,7 +14,31 @@ pub enum UserEvent {
         when: Instant,
         cumulative_pass_nr: u64,
     },
-    Server(Signal),
+    Server(SessionId, Signal),
+    /// Requests were pushed to the empty signal queue
+    SignalQueued,
+    SettingsChanged,
+    /// Request from the health endpoint to compute the pressure amplitude at the points
+    FieldQuery {
+        points: Vec<Vector3>,
+        result: oneshot::Sender<Result<Vec<f32>, String>>,
+    },
+}
+
+/// Sends events to the winit event loop, or to the loop of `Simulator::run_headless`
+#[derive(Clone)]
+pub enum EventProxy {
+    EventLoop(EventLoopProxy<UserEvent>),
+    Channel(Sender<UserEvent>),
+}
+
+impl EventProxy {
+    pub fn send_event(&self, event: UserEvent) -> Result<(), EventLoopClosed<UserEvent>> {
+        match self {
+            EventProxy::EventLoop(proxy) => proxy.send_event(event),
+            EventProxy::Channel(sender) => sender.send(event).map_err(|e| EventLoopClosed(e.0)),
+        }
+    }
 }
 
 pub enum EventResult {
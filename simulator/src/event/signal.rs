@@ -1,5 +1,8 @@
 use autd3_driver::{firmware::cpu::TxMessage, geometry::Geometry};
 
+/// Client that sent a signal. Always `None` unless the server accepts multiple clients.
+pub type SessionId = Option<std::net::SocketAddr>;
+
 pub enum Signal {
     ConfigGeometry(Geometry),
     UpdateGeometry(Geometry),
@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+use autd3_driver::defined::mm;
+
+use crate::{emulator::TransState, Vector3};
+
+const ULTRASOUND_FREQ: f32 = 40000.;
+const T4010A1_AMPLITUDE: f32 = 55114.85; // [Pa*mm]
+const P0: f32 = T4010A1_AMPLITUDE / (4. * PI);
+
+/// Compute the acoustic pressure amplitude at each of `points` on the CPU
+///
+/// This is the same point source model as the slice shader, so it gives the same values as the GPU without a renderer.
+/// `positions` and `states` are the transducer positions and drive states, and `sound_speed` is in the unit of [`State::sound_speed`](crate::State::sound_speed).
+pub fn pressure_at(
+    positions: &[Vector3],
+    states: &[TransState],
+    sound_speed: f32,
+    points: &[Vector3],
+) -> Vec<f32> {
+    let scale = 1. / mm;
+    let wavenumber = 2. * PI * ULTRASOUND_FREQ / (sound_speed * scale);
+    points
+        .iter()
+        .map(|&point| {
+            let point = point * scale;
+            let (re, im) =
+                positions
+                    .iter()
+                    .zip(states)
+                    .fold((0f32, 0f32), |(re, im), (&pos, state)| {
+                        let r = (pos * scale).distance(point);
+                        let p = -state.phase - wavenumber * r;
+                        let a = state.enable * P0 * state.amp / r;
+                        (re + a * p.cos(), im + a * p.sin())
+                    });
+            (re * re + im * im).sqrt()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOUND_SPEED: f32 = 340e3 * mm;
+
+    fn state(amp: f32, phase: f32) -> TransState {
+        TransState {
+            amp,
+            phase,
+            enable: 1.,
+            alpha: 1.,
+        }
+    }
+
+    #[test]
+    fn on_axis_pressure() {
+        let points = [100., 200., 300.].map(|z| Vector3::new(0., 0., z * mm));
+        let result = pressure_at(&[Vector3::ZERO], &[state(1., 0.)], SOUND_SPEED, &points);
+        [100., 200., 300.]
+            .into_iter()
+            .zip(result)
+            .for_each(|(z, p)| {
+                let expect = T4010A1_AMPLITUDE / (4. * PI * z);
+                assert!((expect - p).abs() < expect * 1e-4, "{} != {}", expect, p);
+            });
+    }
+
+    #[test]
+    fn pressure_scales_with_amplitude() {
+        let point = [Vector3::new(0., 0., 150. * mm)];
+        let full = pressure_at(&[Vector3::ZERO], &[state(1., 0.)], SOUND_SPEED, &point)[0];
+        let half = pressure_at(&[Vector3::ZERO], &[state(0.5, 0.)], SOUND_SPEED, &point)[0];
+        assert!((full / 2. - half).abs() < full * 1e-4);
+    }
+
+    #[test]
+    fn symmetric_transducers_interfere() {
+        let positions = [
+            Vector3::new(-10. * mm, 0., 0.),
+            Vector3::new(10. * mm, 0., 0.),
+        ];
+        let point = [Vector3::new(0., 0., 150. * mm)];
+        let single = pressure_at(&positions[..1], &[state(1., 0.)], SOUND_SPEED, &point)[0];
+
+        let in_phase = pressure_at(
+            &positions,
+            &[state(1., 0.), state(1., 0.)],
+            SOUND_SPEED,
+            &point,
+        )[0];
+        assert!((2. * single - in_phase).abs() < single * 1e-4);
+
+        let anti_phase = pressure_at(
+            &positions,
+            &[state(1., 0.), state(1., PI)],
+            SOUND_SPEED,
+            &point,
+        )[0];
+        assert!(anti_phase < single * 1e-4);
+    }
+
+    #[test]
+    fn disabled_transducer_has_no_pressure() {
+        let mut disabled = state(1., 0.);
+        disabled.enable = 0.;
+        let result = pressure_at(
+            &[Vector3::ZERO],
+            &[disabled],
+            SOUND_SPEED,
+            &[Vector3::new(0., 0., 100. * mm)],
+        );
+        assert_eq!(vec![0.], result);
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use autd3_driver::{
+    autd3_device::AUTD3,
+    geometry::{Geometry, IntoDevice, Quaternion, UnitQuaternion, Vector3},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::transform::{to_gl_pos, to_gl_rot},
+    emulator::EmulatorWrapper,
+    error::Result,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DevicePose {
+    pub position: [f32; 3],
+    #[serde(default = "DevicePose::identity")]
+    pub rotation: [f32; 4],
+}
+
+impl DevicePose {
+    const fn identity() -> [f32; 4] {
+        [1., 0., 0., 0.]
+    }
+}
+
+pub fn load_geometry(path: impl AsRef<Path>) -> Result<Geometry> {
+    let file = File::open(path)?;
+    let poses: Vec<DevicePose> = serde_json::from_reader(BufReader::new(file))?;
+    Ok(Geometry::new(
+        poses
+            .into_iter()
+            .enumerate()
+            .map(|(i, pose)| {
+                let [x, y, z] = pose.position;
+                let [w, i_, j, k] = pose.rotation;
+                AUTD3::new(Vector3::new(x, y, z))
+                    .with_rotation(UnitQuaternion::from_quaternion(Quaternion::new(
+                        w, i_, j, k,
+                    )))
+                    .into_device(i as _)
+            })
+            .collect(),
+        4,
+    ))
+}
+
+pub fn grid_geometry(num_devices: usize, cols: usize) -> Geometry {
+    let cols = cols.max(1);
+    Geometry::new(
+        (0..num_devices)
+            .map(|i| {
+                AUTD3::new(Vector3::new(
+                    (i % cols) as f32 * AUTD3::DEVICE_WIDTH,
+                    (i / cols) as f32 * AUTD3::DEVICE_HEIGHT,
+                    0.,
+                ))
+                .into_device(i as _)
+            })
+            .collect(),
+        4,
+    )
+}
+
+pub fn save_geometry(path: impl AsRef<Path>, emulator: &EmulatorWrapper) -> Result<()> {
+    let transducers = emulator.transducers();
+    let poses = transducers
+        .device_ranges()
+        .map(|range| {
+            let pos = to_gl_pos(transducers.positions()[range.start].truncate());
+            let rot = to_gl_rot(transducers.rotations()[range.start]);
+            DevicePose {
+                position: pos.to_array(),
+                rotation: [rot.w, rot.x, rot.y, rot.z],
+            }
+        })
+        .collect::<Vec<_>>();
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &poses)?;
+    Ok(())
+}
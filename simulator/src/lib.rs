@@ -2,13 +2,21 @@ mod common;
 mod emulator;
 mod error;
 mod event;
+mod field;
+mod geometry_file;
 mod renderer;
 mod server;
 mod simulator;
 mod state;
 mod update_flag;
+mod watcher;
 
-pub use simulator::Simulator;
+pub use emulator::TransState;
+pub use field::pressure_at;
+pub use geometry_file::{grid_geometry, load_geometry};
+pub use renderer::{available_gpus, benchmark, BenchmarkResult};
+pub use server::{GrpcOptions, QueuePolicy};
+pub use simulator::{RunOptions, Simulator};
 pub use state::State;
 
 pub type Vector2 = glam::Vec2;
@@ -1,26 +1,56 @@
 use std::{
     error::Error,
-    fs::{self, File, OpenOptions},
-    io::{BufReader, Write},
-    path::Path,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use clap::Parser;
-use simulator::{Simulator, State};
+use clap::ValueEnum;
+use simulator::{GrpcOptions, QueuePolicy, RunOptions, Simulator, State};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
-where
-    T: std::str::FromStr,
-    T::Err: Error + Send + Sync + 'static,
-    U: std::str::FromStr,
-    U::Err: Error + Send + Sync + 'static,
-{
-    let pos = s
-        .find(',')
-        .ok_or_else(|| format!("no `,` found in `{s}`"))?;
-    Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
+const MAX_WINDOW_SIZE: u32 = 16384;
+
+fn parse_window_size(s: &str) -> Result<(u32, u32), Box<dyn Error + Send + Sync + 'static>> {
+    let (width, height) = s
+        .split_once(',')
+        .or_else(|| s.split_once(['x', 'X']))
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT or WIDTH,HEIGHT, got `{s}`"))?;
+    let parse = |v: &str| -> Result<u32, Box<dyn Error + Send + Sync + 'static>> {
+        let v = v
+            .trim()
+            .parse()
+            .map_err(|_| format!("expected WIDTHxHEIGHT or WIDTH,HEIGHT, got `{s}`"))?;
+        if v == 0 || v > MAX_WINDOW_SIZE {
+            return Err(
+                format!("window size must be between 1 and {MAX_WINDOW_SIZE}, got `{s}`").into(),
+            );
+        }
+        Ok(v)
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_grid(s: &str) -> Result<(usize, usize), Box<dyn Error + Send + Sync + 'static>> {
+    let (cols, rows) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected <cols>x<rows>, got `{s}`"))?;
+    let (cols, rows) = (cols.trim().parse()?, rows.trim().parse()?);
+    if cols == 0 || rows == 0 {
+        return Err(format!("grid size must be positive, got `{s}`").into());
+    }
+    Ok((cols, rows))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum QueuePolicyArg {
+    /// discard the oldest queued frame, its request fails with RESOURCE_EXHAUSTED
+    DropOldest,
+    /// hold the client until there is room
+    Block,
 }
 
 #[derive(Parser)]
@@ -30,13 +60,17 @@ where
 )]
 struct Args {
     /// Windows Size (Optional, if set, overrides settings from file)
-    #[arg(short = 'w', long = "window_size", value_name = "Width,Height" , value_parser = parse_key_val::<u32, u32>)]
+    #[arg(short = 'w', long = "window_size", value_name = "Width,Height", value_parser = parse_window_size)]
     window_size: Option<(u32, u32)>,
 
     /// Port (Optional, if set, overrides settings from file)
     #[arg(short = 'p', long = "port")]
     port: Option<u16>,
 
+    /// Unix domain socket path to listen on instead of the TCP port (Optional, if set, overrides settings from file)
+    #[arg(long = "uds")]
+    uds: Option<String>,
+
     /// Vsync (Optional, if set, overrides settings from file)
     #[arg(short = 'v', long = "vsync")]
     vsync: Option<bool>,
@@ -53,6 +87,97 @@ struct Args {
     #[arg(long = "lightweight", default_value = "false")]
     lightweight: Option<bool>,
 
+    /// Geometry file to preview before any client connects
+    #[arg(short = 'g', long = "geometry")]
+    geometry: Option<String>,
+
+    /// Number of AUTD3 devices to preview before any client connects
+    #[arg(long = "devices", conflicts_with = "geometry")]
+    devices: Option<usize>,
+
+    /// Layout of the preview devices (Optional, defaults to a single row)
+    #[arg(long = "grid", value_name = "<cols>x<rows>", value_parser = parse_grid, conflicts_with = "geometry")]
+    grid: Option<(usize, usize)>,
+
+    /// Reload the settings file when it is changed on disk
+    #[arg(long = "watch", default_value = "false")]
+    watch: bool,
+
+    /// Port of the HTTP health endpoint (Optional, disabled if not set).
+    /// `GET /health` returns the status, and `POST /field` with `{"points": [[x, y, z], ...]}` returns the pressure amplitude at the points.
+    #[arg(long = "health-port")]
+    health_port: Option<u16>,
+
+    /// Record received client messages to the file
+    #[arg(long = "record")]
+    record: Option<String>,
+
+    /// Replay client messages recorded with --record instead of waiting for a client
+    #[arg(long = "replay", conflicts_with = "record")]
+    replay: Option<String>,
+
+    /// Loop the replay
+    #[arg(long = "replay-loop", default_value = "false", requires = "replay")]
+    replay_loop: bool,
+
+    /// HTTP/2 keepalive ping interval of the gRPC server in s (Optional, disabled if not set)
+    #[arg(long = "keepalive_interval")]
+    keepalive_interval: Option<u64>,
+
+    /// Timeout of the HTTP/2 keepalive ping in s (Optional, 20 s if not set)
+    #[arg(long = "keepalive_timeout")]
+    keepalive_timeout: Option<u64>,
+
+    /// Maximum number of requests processed at once on each client connection; the number of connections is not limited (Optional, unlimited if not set)
+    #[arg(long = "concurrency_limit_per_connection")]
+    concurrency_limit_per_connection: Option<usize>,
+
+    /// Number of frames that may wait to be applied
+    #[arg(long = "queue-depth", default_value = "8")]
+    queue_depth: usize,
+
+    /// What to do with a new frame when the queue is full
+    #[arg(long = "queue-policy", value_enum, default_value = "drop-oldest")]
+    queue_policy: QueuePolicyArg,
+
+    /// Accept several clients at once, each driving its own devices
+    #[arg(long = "multi-client", default_value = "false")]
+    multi_client: bool,
+
+    /// Measure the slice field computation throughput on the GPU and exit (uses --devices, 1 if not set)
+    #[arg(long = "benchmark", default_value = "false")]
+    benchmark: bool,
+
+    /// Duration of --benchmark in s
+    #[arg(
+        long = "benchmark-duration",
+        default_value = "5",
+        requires = "benchmark"
+    )]
+    benchmark_duration: u64,
+
+    /// Run the server and the emulators without a window until Ctrl+C is pressed.
+    /// The slice display, the control panel and screenshots are not available in this mode.
+    /// Field queries on the health endpoint (--health-port) are computed on the CPU.
+    #[arg(long = "no-gui", default_value = "false", conflicts_with = "benchmark")]
+    no_gui: bool,
+
+    /// Write the default settings to the file and exit
+    #[arg(long = "write-default-config", value_name = "PATH")]
+    write_default_config: Option<String>,
+
+    /// Overwrite an existing file with --write-default-config
+    #[arg(
+        long = "force",
+        default_value = "false",
+        requires = "write_default_config"
+    )]
+    force: bool,
+
+    /// List available GPUs and exit
+    #[arg(long = "list-gpus", default_value = "false")]
+    list_gpus: bool,
+
     /// Debug mode
     #[arg(short = 'd', long = "debug", default_value = "false")]
     debug: bool,
@@ -61,6 +186,55 @@ struct Args {
 fn main() -> anyhow::Result<()> {
     let arg = Args::parse();
 
+    if arg.list_gpus {
+        simulator::available_gpus()
+            .iter()
+            .enumerate()
+            .for_each(|(i, info)| {
+                println!(
+                    "{}: {} ({:?}, {:?})",
+                    i, info.name, info.device_type, info.backend
+                );
+            });
+        return Ok(());
+    }
+
+    if let Some(path) = &arg.write_default_config {
+        let path = Path::new(path);
+        if path.exists() && !arg.force {
+            anyhow::bail!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            );
+        }
+        State::default().save(path)?;
+        println!("Default settings are written to {}", path.display());
+        return Ok(());
+    }
+
+    if arg.benchmark {
+        let devices = arg.devices.unwrap_or(1);
+        if devices == 0 {
+            anyhow::bail!("--benchmark needs at least one device");
+        }
+        let result = simulator::benchmark(devices, Duration::from_secs(arg.benchmark_duration))?;
+        println!(
+            "GPU: {} ({:?}, {:?})",
+            result.adapter.name, result.adapter.device_type, result.adapter.backend
+        );
+        println!("Transducers: {}", result.num_transducers);
+        println!("Pixels per dispatch: {}", result.pixels);
+        println!("Dispatches: {}", result.dispatches);
+        println!("Throughput: {:.3e} pixels/s", result.pixels_per_sec());
+        println!(
+            "Latency: mean {:?}, min {:?}, max {:?}",
+            result.mean_latency(),
+            result.min_latency,
+            result.max_latency
+        );
+        return Ok(());
+    }
+
     let port = arg.port;
     let window_size = arg.window_size;
     let settings_path = if let Some(path) = &arg.setting_dir {
@@ -112,8 +286,12 @@ fn main() -> anyhow::Result<()> {
     if let Some(port) = port {
         state.port = port;
     }
+    if let Some(uds) = &arg.uds {
+        state.uds = Some(uds.clone());
+    }
     if let Some(window_size) = window_size {
         state.window_size = window_size;
+        state.maximized = false;
     }
     if let Some(vsync) = vsync {
         state.vsync = vsync;
@@ -125,22 +303,89 @@ fn main() -> anyhow::Result<()> {
         state.lightweight = lightweight;
     }
 
-    let event_loop = winit::event_loop::EventLoop::with_user_event().build()?;
-    let state = Simulator::run(event_loop, state)?;
-
-    {
-        let settings_str = serde_json::to_string_pretty(&state)?;
-        if settings_path.exists() {
-            fs::remove_file(&settings_path)?;
+    let preview_geometry = match (arg.devices, arg.grid) {
+        (None, None) => arg
+            .geometry
+            .as_ref()
+            .map(simulator::load_geometry)
+            .transpose()?,
+        (Some(devices), None) => Some(simulator::grid_geometry(devices, devices)),
+        (devices, Some((cols, rows))) => {
+            let devices = devices.unwrap_or(cols * rows);
+            if devices > cols * rows {
+                anyhow::bail!("{} devices do not fit in a {}x{} grid", devices, cols, rows);
+            }
+            Some(simulator::grid_geometry(devices, cols))
         }
-        std::fs::create_dir_all(settings_path.parent().unwrap())?;
-        let mut file = OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .append(false)
-            .open(&settings_path)?;
-        write!(file, "{}", settings_str)?;
-    }
+    };
+
+    let options = RunOptions {
+        preview_geometry,
+        settings_path: Some(settings_path.clone()),
+        watch_settings: arg.watch,
+        health_port: arg.health_port,
+        record: arg.record.as_ref().map(PathBuf::from),
+        replay: arg.replay.as_ref().map(PathBuf::from),
+        replay_loop: arg.replay_loop,
+        grpc: GrpcOptions {
+            keepalive_interval: arg.keepalive_interval.map(Duration::from_secs),
+            keepalive_timeout: arg.keepalive_timeout.map(Duration::from_secs),
+            concurrency_limit_per_connection: arg.concurrency_limit_per_connection,
+        },
+        multi_client: arg.multi_client,
+        queue_depth: arg.queue_depth,
+        queue_policy: match arg.queue_policy {
+            QueuePolicyArg::DropOldest => QueuePolicy::DropOldest,
+            QueuePolicyArg::Block => QueuePolicy::Block,
+        },
+    };
+    let state = if arg.no_gui {
+        Simulator::run_headless(state, options)?
+    } else {
+        let event_loop = winit::event_loop::EventLoop::with_user_event().build()?;
+        Simulator::run(event_loop, state, options)?
+    };
+
+    state.save(&settings_path)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_window_size_valid() {
+        assert_eq!((800, 600), parse_window_size("800x600").unwrap());
+        assert_eq!((800, 600), parse_window_size("800X600").unwrap());
+        assert_eq!((800, 600), parse_window_size("800,600").unwrap());
+        assert_eq!((800, 600), parse_window_size(" 800 x 600 ").unwrap());
+        assert_eq!(
+            (1, MAX_WINDOW_SIZE),
+            parse_window_size(&format!("1x{MAX_WINDOW_SIZE}")).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_window_size_missing_separator() {
+        assert!(parse_window_size("800").is_err());
+        assert!(parse_window_size("800600").is_err());
+        assert!(parse_window_size("").is_err());
+    }
+
+    #[test]
+    fn parse_window_size_out_of_range() {
+        assert!(parse_window_size("0x600").is_err());
+        assert!(parse_window_size("800x0").is_err());
+        assert!(parse_window_size(&format!("800x{}", MAX_WINDOW_SIZE + 1)).is_err());
+    }
+
+    #[test]
+    fn parse_window_size_non_numeric() {
+        assert!(parse_window_size("axb").is_err());
+        assert!(parse_window_size("800xb").is_err());
+        assert!(parse_window_size("-800x600").is_err());
+        assert!(parse_window_size("800x600x1").is_err());
+    }
+}
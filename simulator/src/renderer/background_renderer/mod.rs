@@ -0,0 +1,271 @@
+use bytemuck::{Pod, Zeroable};
+use egui_wgpu::wgpu;
+use std::borrow::Cow;
+use wgpu::{util::DeviceExt, Device, Queue, RenderPass, SurfaceConfiguration};
+
+use crate::state::{BackgroundMode, State};
+
+use super::DepthTexture;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Config {
+    top: [f32; 4],
+    bottom: [f32; 4],
+    use_image: u32,
+    _pad: [u32; 3],
+}
+
+fn to_rgba(color: egui::Color32) -> [f32; 4] {
+    [
+        color[0] as f32 / 255.,
+        color[1] as f32 / 255.,
+        color[2] as f32 / 255.,
+        color[3] as f32 / 255.,
+    ]
+}
+
+pub struct BackgroundRenderer {
+    config_buf: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    image_path: Option<String>,
+    use_image: bool,
+}
+
+impl BackgroundRenderer {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        surface_config: &SurfaceConfiguration,
+        sample_count: u32,
+        state: &State,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(size_of::<Config>() as _),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let config_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background Config Buffer"),
+            contents: bytemuck::bytes_of(&Config::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = Self::create_bind_group(
+            device,
+            queue,
+            &bind_group_layout,
+            &config_buf,
+            &sampler,
+            1,
+            1,
+            &[255, 255, 255, 255],
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.view_formats[0],
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let mut renderer = Self {
+            config_buf,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            image_path: None,
+            use_image: false,
+        };
+        renderer.update(device, queue, state);
+        renderer
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_bind_group(
+        device: &Device,
+        queue: &Queue,
+        layout: &wgpu::BindGroupLayout,
+        config_buf: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        width: u32,
+        height: u32,
+        texels: &[u8],
+    ) -> wgpu::BindGroup {
+        let texture_extent = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: texture_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            texture_extent,
+        );
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: config_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    fn load_image(&mut self, device: &Device, queue: &Queue, path: &str) {
+        if self.image_path.as_deref() == Some(path) {
+            return;
+        }
+        self.image_path = Some(path.to_owned());
+        self.use_image = false;
+
+        let image = match image::open(path) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                tracing::warn!("Failed to load background image ({}): {}", path, e);
+                return;
+            }
+        };
+        self.bind_group = Self::create_bind_group(
+            device,
+            queue,
+            &self.bind_group_layout,
+            &self.config_buf,
+            &self.sampler,
+            image.width(),
+            image.height(),
+            &image,
+        );
+        self.use_image = true;
+    }
+
+    pub fn update(&mut self, device: &Device, queue: &Queue, state: &State) {
+        let use_image = match state.background_mode {
+            BackgroundMode::Image if !state.background_image.is_empty() => {
+                self.load_image(device, queue, &state.background_image);
+                self.use_image
+            }
+            _ => false,
+        };
+        let config = Config {
+            top: to_rgba(state.background),
+            bottom: to_rgba(state.background_bottom),
+            use_image: use_image as u32,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.config_buf, 0, bytemuck::bytes_of(&config));
+    }
+
+    pub fn render(&self, rpass: &mut RenderPass) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
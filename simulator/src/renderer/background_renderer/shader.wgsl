@@ -0,0 +1,39 @@
+struct Config {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+    use_image: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0)
+@binding(0)
+var<uniform> config: Config;
+
+@group(0)
+@binding(1)
+var r_image: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var r_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    var result: VertexOutput;
+    result.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 1.0, 1.0);
+    result.uv = uv;
+    return result;
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    if config.use_image != 0u {
+        return textureSample(r_image, r_sampler, vertex.uv);
+    }
+    return mix(config.top, config.bottom, vertex.uv.y);
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    emulator::EmulatorWrapper,
+    error::{Result, SimulatorError},
+    geometry_file::grid_geometry,
+    state::SliceState,
+    State,
+};
+
+use super::slice_renderer::SliceRenderer;
+
+pub struct BenchmarkResult {
+    pub adapter: wgpu::AdapterInfo,
+    pub num_transducers: usize,
+    pub pixels: u64,
+    pub dispatches: u32,
+    pub elapsed: Duration,
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl BenchmarkResult {
+    pub fn pixels_per_sec(&self) -> f64 {
+        (self.pixels * self.dispatches as u64) as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn mean_latency(&self) -> Duration {
+        self.elapsed / self.dispatches.max(1)
+    }
+}
+
+/// Run the slice field computation repeatedly for `duration` without presenting anything
+pub fn benchmark(num_devices: usize, duration: Duration) -> Result<BenchmarkResult> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()?
+        .block_on(run(num_devices, duration))
+}
+
+async fn run(num_devices: usize, duration: Duration) -> Result<BenchmarkResult> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        ..Default::default()
+    });
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        })
+        .await
+        .ok_or(SimulatorError::NoSuitableAdapter)?;
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                required_limits: Default::default(),
+                memory_hints: Default::default(),
+            },
+            None,
+        )
+        .await?;
+
+    let mut emulator = EmulatorWrapper::new(Default::default(), Default::default());
+    emulator.initialize(None, &grid_geometry(num_devices, num_devices), &[]);
+    emulator.iter_mut().for_each(|dev| {
+        dev.transducers.iter_mut().enumerate().for_each(|(i, tr)| {
+            tr.amp = 1.;
+            tr.phase = i as f32 * 0.1;
+            tr.enable = 1.;
+        })
+    });
+
+    let mut state = State::default();
+    state.slice.size.x = SliceState::MAX_SIZE;
+    state.slice.size.y = SliceState::MAX_SIZE;
+
+    // the surface format only matters for the render pipeline, which is never used here
+    let surface_config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: wgpu::TextureFormat::Bgra8UnormSrgb,
+        width: 1,
+        height: 1,
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 0,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
+    };
+    let mut slice_renderer = SliceRenderer::new(&device, &surface_config, 1);
+    slice_renderer.initialize(&device, &emulator);
+    slice_renderer.update_trans_pos(&emulator, &queue);
+    slice_renderer.update_trans_state(&emulator, &queue);
+    slice_renderer.update_config(&state, &emulator, &queue);
+    slice_renderer.update_slice(&state, &queue);
+
+    let mut dispatch = || {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            slice_renderer.compute(&mut pass);
+        }
+        let start = Instant::now();
+        queue.submit(Some(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        start.elapsed()
+    };
+
+    // warm up
+    dispatch();
+
+    let mut dispatches = 0;
+    let mut elapsed = Duration::ZERO;
+    let mut min_latency = Duration::MAX;
+    let mut max_latency = Duration::ZERO;
+    while elapsed < duration {
+        let latency = dispatch();
+        dispatches += 1;
+        elapsed += latency;
+        min_latency = min_latency.min(latency);
+        max_latency = max_latency.max(latency);
+    }
+
+    let (width, height) = slice_renderer.resolution();
+    Ok(BenchmarkResult {
+        adapter: adapter.get_info(),
+        num_transducers: emulator.transducers().len(),
+        pixels: width as u64 * height as u64,
+        dispatches,
+        elapsed,
+        min_latency,
+        max_latency,
+    })
+}
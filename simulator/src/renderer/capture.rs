@@ -0,0 +1,85 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::RgbaImage;
+use wgpu::{Device, Queue};
+
+use crate::error::Result;
+
+/// Read back a `Bgra8UnormSrgb` texture that has `COPY_SRC` usage as an RGBA image
+pub fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage> {
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, move |r| {
+        let _ = tx.send(r);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    rx.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    let pixels = slice
+        .get_mapped_range()
+        .chunks(padded_bytes_per_row as usize)
+        .flat_map(|row| {
+            row[..unpadded_bytes_per_row as usize]
+                .chunks(4)
+                .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    buffer.unmap();
+
+    Ok(RgbaImage::from_raw(width, height, pixels).unwrap())
+}
+
+/// Save the image as a PNG with the given key-value pairs as tEXt chunks
+pub fn save_png(path: &Path, image: &RgbaImage, text: &[(&str, String)]) -> Result<()> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        image.width(),
+        image.height(),
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    text.iter()
+        .try_for_each(|(key, value)| encoder.add_text_chunk(key.to_string(), value.clone()))?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(())
+}
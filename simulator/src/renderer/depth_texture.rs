@@ -7,7 +7,7 @@ pub struct DepthTexture {
 impl DepthTexture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn new(device: &Device, surface_config: &SurfaceConfiguration) -> Self {
+    pub fn new(device: &Device, surface_config: &SurfaceConfiguration, sample_count: u32) -> Self {
         let size = wgpu::Extent3d {
             width: surface_config.width.max(1),
             height: surface_config.height.max(1),
@@ -17,7 +17,7 @@ impl DepthTexture {
             label: None,
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
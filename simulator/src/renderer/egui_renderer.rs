@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -25,14 +26,28 @@ use winit::event_loop::EventLoopProxy;
 use winit::window::Window;
 
 use crate::common::color_map::ColorMap;
-use crate::emulator::EmulatorWrapper;
+use crate::common::export::write_modulation_csv;
+use crate::common::picking::{to_screen, Aabb, Ray};
+use crate::common::transform::to_gl_pos;
+use crate::common::unit::{format_duration, format_freq};
+use crate::emulator::{pwm_out, EmulatorWrapper};
 use crate::event::{EventResult, UserEvent};
-use crate::state::Tab;
+use crate::geometry_file::save_geometry;
+use crate::state::{
+    default_tint, BackgroundMode, SliceField, SliceState, Tab, TimeUnit, TransducerColoring,
+    UpAxis, DEFAULT_FONT_SIZE, LAYOUT_FILE,
+};
 use crate::update_flag::UpdateFlag;
-use crate::{error::SimulatorError, Vector3, ZPARITY};
+use crate::{error::SimulatorError, Matrix4, Vector2, Vector3};
 
 const MIN_COL_WIDTH: f32 = 120.;
 const SPACING: [f32; 2] = [2.0, 4.0];
+const CUSTOM_FONT: &str = "custom";
+const DEVICE_PICK_MARGIN: f32 = 5. * mm;
+const DEFAULT_CAPTURE_SIZE: (u32, u32) = (3840, 2160);
+const MAX_CAPTURE_SIZE: u32 = 16384;
+const MOD_PLOT_DEFAULT_WINDOW: usize = 65536;
+const MOD_PLOT_MAX_POINTS: usize = 2048;
 
 pub struct EguiRenderer {
     beginning: Instant,
@@ -45,6 +60,8 @@ pub struct EguiRenderer {
     close: bool,
     is_first_frame: bool,
     initial_state: String,
+    waiting_since: Option<Instant>,
+    font: Option<(String, f32)>,
 }
 
 impl EguiRenderer {
@@ -85,6 +102,8 @@ impl EguiRenderer {
         let mut info = ViewportInfo::default();
         egui_winit::update_viewport_info(&mut info, egui_winit.egui_ctx(), &window, true);
 
+        Self::load_layout(egui_winit.egui_ctx(), state);
+
         Self {
             beginning: Instant::now(),
             egui_winit,
@@ -96,6 +115,8 @@ impl EguiRenderer {
             close: false,
             is_first_frame: true,
             initial_state: serde_json::to_string(state).unwrap(),
+            waiting_since: None,
+            font: None,
         }
     }
 
@@ -108,6 +129,105 @@ impl EguiRenderer {
         egui_ctx
     }
 
+    fn load_layout(ctx: &egui::Context, state: &crate::State) {
+        let path = state.layout_path();
+        if !path.exists() {
+            let Some(old_path) = state
+                .layout_dir
+                .as_ref()
+                .map(|dir| Path::new(dir).join(LAYOUT_FILE))
+                .filter(|old_path| old_path.exists())
+            else {
+                return;
+            };
+            match std::fs::copy(&old_path, &path) {
+                Ok(_) => tracing::info!("Window layout is migrated from {}", old_path.display()),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to migrate window layout from {}: {}",
+                        old_path.display(),
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+        let memory = match std::fs::read_to_string(&path)
+            .map_err(SimulatorError::from)
+            .and_then(|s| Ok(serde_json::from_str::<egui::Memory>(&s)?))
+        {
+            Ok(memory) => memory,
+            Err(e) => {
+                tracing::warn!("Failed to load window layout ({}): {}", path.display(), e);
+                return;
+            }
+        };
+        ctx.memory_mut(|m| {
+            let options = std::mem::take(&mut m.options);
+            *m = memory;
+            m.options = options;
+        });
+    }
+
+    fn update_font(&mut self, state: &crate::State) {
+        let font = (state.font_path.clone(), state.font_size);
+        if self.font.as_ref() == Some(&font) {
+            return;
+        }
+
+        let mut fonts = egui::FontDefinitions::default();
+        if !font.0.is_empty() {
+            match std::fs::read(&font.0) {
+                Ok(data) => {
+                    fonts.font_data.insert(
+                        CUSTOM_FONT.to_owned(),
+                        Arc::new(egui::FontData::from_owned(data)),
+                    );
+                    // the embedded fonts remain as fallbacks for glyphs the custom font lacks
+                    fonts
+                        .families
+                        .values_mut()
+                        .for_each(|family| family.insert(0, CUSTOM_FONT.to_owned()));
+                }
+                Err(e) => tracing::warn!("Failed to load font ({}): {}", font.0, e),
+            }
+        }
+
+        let ctx = self.context();
+        ctx.set_fonts(fonts);
+        let scale = font.1 / DEFAULT_FONT_SIZE;
+        ctx.style_mut(|style| {
+            style.text_styles = egui::Style::default()
+                .text_styles
+                .into_iter()
+                .map(|(text_style, mut font_id)| {
+                    font_id.size *= scale;
+                    (text_style, font_id)
+                })
+                .collect();
+        });
+
+        self.font = Some(font);
+    }
+
+    pub fn save_layout(&self, state: &mut crate::State) {
+        let path = state.layout_path();
+        let result = self
+            .context()
+            .memory(|m| serde_json::to_string(m))
+            .map_err(SimulatorError::from)
+            .and_then(|s| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                Ok(std::fs::write(&path, s)?)
+            });
+        match result {
+            Ok(()) => state.layout_dir = Some(state.settings_dir.clone()),
+            Err(e) => tracing::warn!("Failed to save window layout ({}): {}", path.display(), e),
+        }
+    }
+
     pub fn context(&self) -> &egui::Context {
         self.egui_winit.egui_ctx()
     }
@@ -124,6 +244,7 @@ impl EguiRenderer {
         &mut self,
         mut raw_input: egui::RawInput,
         waiting: bool,
+        view_proj: Matrix4,
         state: &mut crate::State,
         emulator: &mut EmulatorWrapper,
         update_flag: &mut UpdateFlag,
@@ -132,11 +253,19 @@ impl EguiRenderer {
 
         let close_requested = raw_input.viewport().close_requested();
 
+        self.update_font(state);
+
+        self.waiting_since = if waiting {
+            self.waiting_since.or_else(|| Some(Instant::now()))
+        } else {
+            None
+        };
+
         let full_output = self.egui_winit.egui_ctx().run(raw_input, |egui_ctx| {
             if waiting {
-                self._waiting(egui_ctx);
+                self._waiting(egui_ctx, state);
             } else {
-                self._update(egui_ctx, state, emulator, update_flag);
+                self._update(egui_ctx, view_proj, state, emulator, update_flag);
             }
         });
 
@@ -163,6 +292,7 @@ impl EguiRenderer {
         window_surface_view: &TextureView,
         screen_descriptor: ScreenDescriptor,
         window: &Window,
+        view_proj: Matrix4,
         state: &mut crate::State,
         emulator: &mut EmulatorWrapper,
         update_flag: &mut UpdateFlag,
@@ -187,6 +317,7 @@ impl EguiRenderer {
         let full_output = self.update(
             raw_input,
             !emulator.initialized(),
+            view_proj,
             state,
             emulator,
             update_flag,
@@ -362,7 +493,14 @@ impl EguiRenderer {
             egui::Event::MouseWheel { delta, .. } => Some(*delta),
             _ => None,
         }) {
-            let trans = -f * mouse_wheel.y * state.camera.move_speed * 10. * ZPARITY;
+            let direction = if state.camera.invert_zoom { -1. } else { 1. };
+            let trans = -f
+                * mouse_wheel.y
+                * direction
+                * state.camera.zoom_sensitivity
+                * state.camera.move_speed
+                * 10.
+                * state.zparity();
             state.camera.pos += trans;
             update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
         }
@@ -379,8 +517,10 @@ impl EguiRenderer {
                     state.camera.pos.z += trans.z;
                     update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
                 } else {
-                    let delta_x = -mouse_delta[0] * state.camera.move_speed / METER * ZPARITY;
-                    let delta_y = -mouse_delta[1] * state.camera.move_speed / METER * ZPARITY;
+                    let delta_x =
+                        -mouse_delta[0] * state.camera.move_speed / METER * state.zparity();
+                    let delta_y =
+                        -mouse_delta[1] * state.camera.move_speed / METER * state.zparity();
 
                     let rot = Quat::from_euler(glam::EulerRot::XYZ, delta_y, delta_x, 0.0);
 
@@ -394,55 +534,353 @@ impl EguiRenderer {
         }
     }
 
+    fn update_time_by_keyboard(
+        input: &InputState,
+        state: &mut crate::State,
+        update_flag: &mut UpdateFlag,
+    ) {
+        if input.key_pressed(egui::Key::Space) {
+            state.auto_play = !state.auto_play;
+            update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+        }
+
+        if state.auto_play {
+            return;
+        }
+
+        if input.key_pressed(egui::Key::ArrowRight) {
+            state.real_time = state.real_time.wrapping_add_signed(state.time_step as _);
+            update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+        }
+        if input.key_pressed(egui::Key::ArrowLeft) {
+            state.real_time = state.real_time.wrapping_add_signed(-state.time_step as _);
+            update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+        }
+    }
+
+    fn draw_legend(ctx: &egui::Context, state: &crate::State) {
+        const BAR_SIZE: [f32; 2] = [16., 200.];
+        const STEPS: usize = 64;
+        const TICKS: usize = 5;
+
+        egui::Area::new(egui::Id::new("legend"))
+            .movable(true)
+            .default_pos(egui::pos2(
+                ctx.screen_rect().right() - 120.,
+                ctx.screen_rect().top() + 20.,
+            ))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let (label, max) = match state.slice.field {
+                        SliceField::Pressure => ("Pressure [Pa]", state.slice.pressure_max),
+                        SliceField::Intensity => ("Intensity [W/m²]", state.slice.intensity_max),
+                    };
+                    ui.label(label);
+                    let (rect, _) =
+                        ui.allocate_exact_size(egui::vec2(80., BAR_SIZE[1]), egui::Sense::hover());
+                    let painter = ui.painter();
+                    let bar = egui::Rect::from_min_size(rect.min, BAR_SIZE.into());
+                    let h = bar.height() / STEPS as f32;
+                    state
+                        .slice
+                        .color_map
+                        .color_map((0..STEPS).map(|i| (i as f64 + 0.5) / STEPS as f64))
+                        .into_iter()
+                        .enumerate()
+                        .for_each(|(i, color)| {
+                            let y = bar.bottom() - (i + 1) as f32 * h;
+                            painter.rect_filled(
+                                egui::Rect::from_min_size(
+                                    egui::pos2(bar.left(), y),
+                                    egui::vec2(bar.width(), h + 0.5),
+                                ),
+                                0.,
+                                egui::Rgba::from_rgb(color.r as _, color.g as _, color.b as _),
+                            );
+                        });
+                    let text_color = ui.visuals().text_color();
+                    (0..TICKS).for_each(|i| {
+                        let t = i as f32 / (TICKS - 1) as f32;
+                        let y = bar.bottom() - t * bar.height();
+                        painter.line_segment(
+                            [egui::pos2(bar.right(), y), egui::pos2(bar.right() + 4., y)],
+                            egui::Stroke::new(1., text_color),
+                        );
+                        painter.text(
+                            egui::pos2(bar.right() + 6., y),
+                            egui::Align2::LEFT_CENTER,
+                            format!("{:.0}", t * max),
+                            egui::FontId::default(),
+                            text_color,
+                        );
+                    });
+                });
+            });
+    }
+
+    fn draw_hud(ctx: &egui::Context, state: &mut crate::State) {
+        let fps = ctx.input(|input| 1. / input.stable_dt.max(f32::EPSILON));
+        let default_pos = state.hud.position.map_or(
+            egui::pos2(
+                ctx.screen_rect().left() + 10.,
+                ctx.screen_rect().bottom() - 90.,
+            ),
+            |(x, y)| egui::pos2(x, y),
+        );
+        let response = egui::Area::new(egui::Id::new("hud"))
+            .movable(true)
+            .default_pos(default_pos)
+            .show(ctx, |ui| {
+                ui.set_opacity(state.hud.opacity);
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("System time: {} ns", state.real_time));
+                    ui.label(format!("FPS: {:.1}", fps));
+                    ui.label(format!(
+                        "Camera: ({:.1}, {:.1}, {:.1}) mm",
+                        state.camera.pos.x / mm,
+                        state.camera.pos.y / mm,
+                        state.camera.pos.z / mm
+                    ));
+                });
+            })
+            .response;
+        state.hud.position = Some((response.rect.left(), response.rect.top()));
+    }
+
+    fn measure_by_mouse(
+        input: &InputState,
+        view_proj: Matrix4,
+        screen: Vector2,
+        state: &mut crate::State,
+    ) {
+        if !input.pointer.primary_clicked() {
+            return;
+        }
+        let Some(pos) = input.pointer.interact_pos() else {
+            return;
+        };
+        let ray = Ray::from_screen(view_proj, Vector2::new(pos.x, pos.y), screen);
+        if let Some(p) = ray.intersect_slice(&state.slice) {
+            if state.measure.points.len() >= 2 {
+                state.measure.points.clear();
+            }
+            state.measure.points.push(p);
+        }
+    }
+
+    fn draw_measure(
+        ctx: &egui::Context,
+        view_proj: Matrix4,
+        screen: Vector2,
+        state: &crate::State,
+    ) {
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Background,
+            egui::Id::new("measure"),
+        ));
+        let color = egui::Color32::YELLOW;
+        let points = state
+            .measure
+            .points
+            .iter()
+            .filter_map(|&p| to_screen(view_proj, p, screen))
+            .map(|p| egui::pos2(p.x, p.y))
+            .collect::<Vec<_>>();
+        points.iter().for_each(|&p| {
+            painter.circle_filled(p, 3., color);
+        });
+        if let ([a, b], [pa, pb]) = (&state.measure.points[..], &points[..]) {
+            painter.line_segment([*pa, *pb], egui::Stroke::new(1.5, color));
+            painter.text(
+                pa.lerp(*pb, 0.5),
+                egui::Align2::LEFT_BOTTOM,
+                format!("{:.2} mm", a.distance(*b) / mm),
+                egui::FontId::default(),
+                color,
+            );
+        }
+    }
+
+    fn device_bounds(emulator: &EmulatorWrapper) -> Vec<Aabb> {
+        let positions = emulator.transducers().positions();
+        emulator
+            .transducers()
+            .device_ranges()
+            .map(|range| {
+                Aabb::from_points(
+                    positions[range].iter().map(|p| p.truncate()),
+                    DEVICE_PICK_MARGIN,
+                )
+            })
+            .collect()
+    }
+
+    fn select_by_mouse(
+        input: &InputState,
+        view_proj: Matrix4,
+        screen: Vector2,
+        state: &mut crate::State,
+        emulator: &EmulatorWrapper,
+    ) -> bool {
+        if !input.pointer.primary_clicked() {
+            return false;
+        }
+        let Some(pos) = input.pointer.interact_pos() else {
+            return false;
+        };
+        let ray = Ray::from_screen(view_proj, Vector2::new(pos.x, pos.y), screen);
+        state.selection.device = Self::device_bounds(emulator)
+            .iter()
+            .enumerate()
+            .filter_map(|(i, aabb)| ray.intersect_aabb(aabb).map(|t| (i, t)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i);
+        if state.selection.device.is_some() {
+            state.tab = Tab::Info;
+            state.selection.scroll = true;
+        }
+        true
+    }
+
+    fn draw_selection(
+        ctx: &egui::Context,
+        view_proj: Matrix4,
+        screen: Vector2,
+        state: &crate::State,
+        emulator: &EmulatorWrapper,
+    ) {
+        let Some(aabb) = state
+            .selection
+            .device
+            .and_then(|dev| Self::device_bounds(emulator).into_iter().nth(dev))
+        else {
+            return;
+        };
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Background,
+            egui::Id::new("selection"),
+        ));
+        let stroke = egui::Stroke::new(2., egui::Color32::ORANGE);
+        aabb.edges().into_iter().for_each(|(a, b)| {
+            // positions are already in GL coordinates, and to_gl_pos is its own inverse
+            if let (Some(a), Some(b)) = (
+                to_screen(view_proj, to_gl_pos(a), screen),
+                to_screen(view_proj, to_gl_pos(b), screen),
+            ) {
+                painter.line_segment([egui::pos2(a.x, a.y), egui::pos2(b.x, b.y)], stroke);
+            }
+        });
+    }
+
     pub(crate) fn _update(
         &self,
         ctx: &egui::Context,
+        view_proj: Matrix4,
         state: &mut crate::State,
         emulator: &mut EmulatorWrapper,
         update_flag: &mut crate::update_flag::UpdateFlag,
     ) {
-        egui::Window::new("Control panel")
-            .resizable(true)
-            .vscroll(true)
-            .default_open(true)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.selectable_value(&mut state.tab, Tab::Slice, "Slice");
-                    ui.selectable_value(&mut state.tab, Tab::Camera, "Camera");
-                    ui.selectable_value(&mut state.tab, Tab::Config, "Config");
-                    ui.selectable_value(&mut state.tab, Tab::Info, "Info");
-                });
-                ui.separator();
-                match state.tab {
-                    Tab::Slice => Self::slice_tab(ui, state, update_flag),
-                    Tab::Camera => Self::camera_tab(ui, state, update_flag),
-                    Tab::Config => Self::config_tab(ui, state, emulator, update_flag),
-                    Tab::Info => Self::info_tab(ui, state, emulator, update_flag),
-                }
-
-                ui.separator();
+        if ctx.input(|input| input.key_pressed(egui::Key::F1)) {
+            state.hide_ui = !state.hide_ui;
+        }
 
-                ui.horizontal(|ui| {
-                    if ui.small_button("Default").clicked() {
-                        state.merge(crate::State::default());
-                        *update_flag = UpdateFlag::all();
+        if !state.hide_ui {
+            egui::Window::new("Control panel")
+                .resizable(true)
+                .vscroll(true)
+                .default_open(true)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut state.tab, Tab::Slice, "Slice");
+                        ui.selectable_value(&mut state.tab, Tab::Camera, "Camera");
+                        ui.selectable_value(&mut state.tab, Tab::Config, "Config");
+                        ui.selectable_value(&mut state.tab, Tab::Info, "Info");
+                    });
+                    ui.separator();
+                    let unlocked = !state.lock_view;
+                    match state.tab {
+                        Tab::Slice => {
+                            ui.add_enabled_ui(unlocked, |ui| {
+                                Self::slice_tab(ui, state, update_flag)
+                            });
+                        }
+                        Tab::Camera => {
+                            ui.add_enabled_ui(unlocked, |ui| {
+                                Self::camera_tab(ui, state, emulator, update_flag)
+                            });
+                        }
+                        Tab::Config => Self::config_tab(ui, state, emulator, update_flag),
+                        Tab::Info => Self::info_tab(ui, state, emulator, update_flag),
                     }
 
-                    if ui.small_button("Reset").clicked() {
-                        let initial_state: crate::State =
-                            serde_json::from_str(&self.initial_state).unwrap();
-                        state.merge(initial_state);
-                        *update_flag = UpdateFlag::all();
-                    }
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.lock_view, "Lock view")
+                            .on_hover_text(
+                                "Ignore the mouse and disable the Slice and Camera tabs",
+                            );
+
+                        if ui.small_button("Default").clicked() {
+                            state.merge(crate::State::default());
+                            *update_flag = UpdateFlag::all();
+                        }
+
+                        if ui.small_button("Reset").clicked() {
+                            let initial_state: crate::State =
+                                serde_json::from_str(&self.initial_state).unwrap();
+                            state.merge(initial_state);
+                            *update_flag = UpdateFlag::all();
+                        }
+                    });
                 });
-            });
+        }
 
-        if !ctx.wants_pointer_input() {
+        if !ctx.wants_pointer_input() && !state.lock_view {
             ctx.input(|input| {
                 Self::update_camera_by_mouse(input, state, update_flag);
             });
         }
 
+        if !ctx.wants_keyboard_input() {
+            ctx.input(|input| {
+                Self::update_time_by_keyboard(input, state, update_flag);
+            });
+        }
+
+        if state.legend && !state.hide_ui {
+            Self::draw_legend(ctx, state);
+        }
+
+        if state.hud.enable && !state.hide_ui {
+            Self::draw_hud(ctx, state);
+        }
+
+        if state.measure.enable {
+            let screen = ctx.screen_rect().size();
+            let screen = Vector2::new(screen.x, screen.y);
+            if !ctx.is_pointer_over_area() {
+                ctx.input(|input| {
+                    Self::measure_by_mouse(input, view_proj, screen, state);
+                });
+            }
+            if !state.hide_ui {
+                Self::draw_measure(ctx, view_proj, screen, state);
+            }
+        } else if emulator.initialized() {
+            let screen = ctx.screen_rect().size();
+            let screen = Vector2::new(screen.x, screen.y);
+            if !ctx.is_pointer_over_area()
+                && ctx
+                    .input(|input| Self::select_by_mouse(input, view_proj, screen, state, emulator))
+            {
+                ctx.request_repaint();
+            }
+            if !state.hide_ui {
+                Self::draw_selection(ctx, view_proj, screen, state, emulator);
+            }
+        }
+
         if state.auto_play {
             update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
             state.real_time = (DcSysTime::now().sys_time() as f64 * state.time_scale as f64) as _;
@@ -538,7 +976,7 @@ impl EguiRenderer {
                 let response = ui.add(
                     DragValue::new(&mut state.slice.size.x)
                         .speed(1.)
-                        .range(1.0..=1024.),
+                        .range(SliceState::MIN_SIZE..=SliceState::MAX_SIZE),
                 );
                 ui.end_row();
 
@@ -547,16 +985,42 @@ impl EguiRenderer {
                     ui.add(
                         DragValue::new(&mut state.slice.size.y)
                             .speed(1.)
-                            .range(1.0..=1024.),
+                            .range(SliceState::MIN_SIZE..=SliceState::MAX_SIZE),
                     ),
                 );
                 ui.end_row();
 
+                ui.label("Pixel size:");
+                let response = response.union(
+                    ui.add(
+                        DragValue::new(&mut state.slice.pixel_size)
+                            .speed(0.1 * mm)
+                            .range(SliceState::MIN_PIXEL_SIZE..=SliceState::MAX_PIXEL_SIZE),
+                    ),
+                );
+                ui.end_row();
+
+                let effective = state.slice.effective_pixel_size();
+                if effective.max_element() > state.slice.pixel_size {
+                    ui.label("Effective pixel size:");
+                    ui.label(
+                        egui::RichText::new(format!("{:.3} x {:.3}", effective.x, effective.y))
+                            .color(egui::Color32::ORANGE),
+                    )
+                    .on_hover_text(format!(
+                        "The slice is limited to {} x {} pixels, so the pixels are larger than the pixel size",
+                        SliceState::MAX_RESOLUTION,
+                        SliceState::MAX_RESOLUTION
+                    ));
+                    ui.end_row();
+                }
+
                 response
             })
             .inner
             .changed()
         {
+            state.slice.clamp_size();
             update_flag.set(UpdateFlag::UPDATE_SLICE_SIZE, true);
         }
 
@@ -584,20 +1048,122 @@ impl EguiRenderer {
                     });
                 ui.end_row();
 
-                ui.label("Max pressure [Pa]:");
-                if ui
-                    .add(
-                        DragValue::new(&mut state.slice.pressure_max)
-                            .speed(100.)
-                            .range(0.0..=f32::MAX),
-                    )
-                    .changed()
-                {
-                    update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                ui.label("Field:");
+                egui::ComboBox::from_id_salt("slice_field")
+                    .selected_text(format!("{:?}", state.slice.field))
+                    .show_ui(ui, |ui| {
+                        [SliceField::Pressure, SliceField::Intensity]
+                            .into_iter()
+                            .for_each(|f| {
+                                if ui
+                                    .selectable_value(&mut state.slice.field, f, format!("{:?}", f))
+                                    .changed()
+                                {
+                                    update_flag.set(UpdateFlag::UPDATE_SLICE_FIELD, true);
+                                }
+                            });
+                    });
+                ui.end_row();
+
+                match state.slice.field {
+                    SliceField::Pressure => {
+                        ui.label("Max pressure [Pa]:");
+                        if ui
+                            .add(
+                                DragValue::new(&mut state.slice.pressure_max)
+                                    .speed(100.)
+                                    .range(0.0..=f32::MAX),
+                            )
+                            .changed()
+                        {
+                            update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                        }
+                    }
+                    SliceField::Intensity => {
+                        ui.label("Max intensity [W/m²]:");
+                        if ui
+                            .add(
+                                DragValue::new(&mut state.slice.intensity_max)
+                                    .speed(100.)
+                                    .range(0.0..=f32::MAX),
+                            )
+                            .changed()
+                        {
+                            update_flag.set(UpdateFlag::UPDATE_SLICE_FIELD, true);
+                        }
+                    }
                 }
                 ui.end_row();
+
+                ui.label("Show legend:");
+                ui.checkbox(&mut state.legend, "");
+                ui.end_row();
+
+                // the threshold is compared with the displayed field
+                ui.label(match state.slice.field {
+                    SliceField::Pressure => "Mask below [Pa]:",
+                    SliceField::Intensity => "Mask below [W/m²]:",
+                });
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut state.slice.mask, "").changed() {
+                        update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                    }
+                    if ui
+                        .add_enabled(
+                            state.slice.mask,
+                            DragValue::new(&mut state.slice.mask_threshold)
+                                .speed(100.)
+                                .range(0.0..=f32::MAX),
+                        )
+                        .changed()
+                    {
+                        update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Carpet plot height:");
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut state.slice.carpet, "").changed() {
+                        update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                    }
+                    if ui
+                        .add_enabled(
+                            state.slice.carpet,
+                            DragValue::new(&mut state.slice.carpet_height).speed(1. * mm),
+                        )
+                        .changed()
+                    {
+                        update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+                    }
+                });
+                ui.end_row();
             });
 
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("Snapshot").clicked() {
+                state.snapshot.request = true;
+                state.snapshot.taken = true;
+            }
+            if ui
+                .add_enabled(
+                    state.snapshot.taken,
+                    egui::Checkbox::new(&mut state.snapshot.show_diff, "Show diff"),
+                )
+                .changed()
+            {
+                update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+            }
+            if ui
+                .add_enabled(state.snapshot.taken, egui::Button::new("Clear"))
+                .clicked()
+            {
+                state.snapshot = Default::default();
+                update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+            }
+        });
+
         ui.separator();
         ui.horizontal(|ui| {
             if ui.button("xy").clicked() {
@@ -621,9 +1187,109 @@ impl EguiRenderer {
                 update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
             }
         });
+
+        ui.separator();
+        ui.label("Focus markers");
+        let mut changed = false;
+        let mut remove = None;
+        egui::Grid::new("slice_marker_grid")
+            .num_columns(2)
+            .min_col_width(MIN_COL_WIDTH)
+            .spacing(SPACING)
+            .striped(true)
+            .show(ui, |ui| {
+                state.markers.iter_mut().enumerate().for_each(|(i, p)| {
+                    ui.label(format!("Marker {}:", i));
+                    ui.horizontal(|ui| {
+                        [("x: ", &mut p.x), ("y: ", &mut p.y), ("z: ", &mut p.z)]
+                            .into_iter()
+                            .for_each(|(prefix, v)| {
+                                changed |= ui
+                                    .add(DragValue::new(v).speed(1. * mm).prefix(prefix))
+                                    .changed();
+                            });
+                        if ui.button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    ui.end_row();
+                });
+            });
+        if let Some(i) = remove {
+            state.markers.remove(i);
+            changed = true;
+        }
+        if ui
+            .button("Add marker")
+            .on_hover_text("Add a marker at the slice center")
+            .clicked()
+        {
+            state.markers.push(state.slice.pos);
+            changed = true;
+        }
+        if changed {
+            update_flag.set(UpdateFlag::UPDATE_MARKERS, true);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.measure.enable, "Measure").changed() {
+                state.measure.points.clear();
+            }
+            if ui.button("Clear").clicked() {
+                state.measure.points.clear();
+            }
+        });
+        if let [a, b] = state.measure.points[..] {
+            ui.label(format!("Distance: {:.2} mm", a.distance(b) / mm));
+        } else if state.measure.enable {
+            ui.label("Click two points on the slice");
+        }
+
+        ui.separator();
+        if Self::clipboard_buttons(ui, &mut state.slice) {
+            update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
+            update_flag.set(UpdateFlag::UPDATE_SLICE_SIZE, true);
+            update_flag.set(UpdateFlag::UPDATE_SLICE_COLOR_MAP, true);
+            update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+        }
+    }
+
+    fn clipboard_buttons<T: serde::Serialize + serde::de::DeserializeOwned>(
+        ui: &mut egui::Ui,
+        value: &mut T,
+    ) -> bool {
+        ui.horizontal(|ui| {
+            if ui.button("Copy as JSON").clicked() {
+                match serde_json::to_string_pretty(value) {
+                    Ok(json) => ui.ctx().copy_text(json),
+                    Err(e) => tracing::warn!("Failed to serialize state: {}", e),
+                }
+            }
+            if ui.button("Paste JSON").clicked() {
+                match arboard::Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.get_text())
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+                {
+                    Ok(v) => {
+                        *value = v;
+                        return true;
+                    }
+                    Err(e) => tracing::warn!("Failed to paste state from clipboard: {}", e),
+                }
+            }
+            false
+        })
+        .inner
     }
 
-    fn camera_tab(ui: &mut egui::Ui, state: &mut crate::State, update_flag: &mut UpdateFlag) {
+    fn camera_tab(
+        ui: &mut egui::Ui,
+        state: &mut crate::State,
+        emulator: &EmulatorWrapper,
+        update_flag: &mut UpdateFlag,
+    ) {
         ui.label("Position");
         if egui::Grid::new("camera_pos_grid")
             .num_columns(2)
@@ -714,8 +1380,58 @@ impl EguiRenderer {
                         .range(1. * mm..=10.0 * mm),
                 );
                 ui.end_row();
+
+                ui.label("Zoom sensitivity:");
+                ui.add(
+                    DragValue::new(&mut state.camera.zoom_sensitivity)
+                        .speed(0.01)
+                        .range(0.01..=10.0),
+                );
+                ui.end_row();
+
+                ui.label("Invert zoom:");
+                ui.checkbox(&mut state.camera.invert_zoom, "");
+                ui.end_row();
+
+                ui.label("Up axis:");
+                egui::ComboBox::from_id_salt("up_axis")
+                    .selected_text(format!("{:?}", state.up_axis))
+                    .show_ui(ui, |ui| {
+                        UpAxis::iter().for_each(|axis| {
+                            if ui
+                                .selectable_value(&mut state.up_axis, axis, format!("{:?}", axis))
+                                .changed()
+                            {
+                                update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+                            }
+                        });
+                    });
+                ui.end_row();
             });
 
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(emulator.initialized(), egui::Button::new("Fit to array"))
+                .clicked()
+            {
+                let zparity = state.zparity();
+                let rotation = state.up_axis.rotation();
+                let positions = emulator
+                    .transducers()
+                    .positions()
+                    .iter()
+                    .map(|p| (rotation * p.truncate()).extend(p.w))
+                    .collect::<Vec<_>>();
+                state.camera.fit(&positions, zparity);
+                update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+            }
+
+            if ui.button("Reset camera").clicked() {
+                state.camera = crate::State::default().camera;
+                update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+            }
+        });
+
         ui.separator();
         ui.label("Perspective");
         if egui::Grid::new("camera_pers_grid")
@@ -760,6 +1476,11 @@ impl EguiRenderer {
         {
             update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
         }
+
+        ui.separator();
+        if Self::clipboard_buttons(ui, &mut state.camera) {
+            update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+        }
     }
 
     fn config_tab(
@@ -776,15 +1497,51 @@ impl EguiRenderer {
             .show(ui, |ui| {
                 ui.label("Sound speed:");
                 if ui
-                    .add(DragValue::new(&mut state.sound_speed).speed(100. * mm))
+                    .add(
+                        DragValue::new(&mut state.sound_speed)
+                            .speed(100. * mm)
+                            .range(1. * mm..=f32::MAX),
+                    )
                     .changed()
                 {
                     update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
                 }
                 ui.end_row();
+
+                ui.label("Density [kg/m³]:");
+                if ui
+                    .add(
+                        DragValue::new(&mut state.density)
+                            .speed(0.01)
+                            .range(f32::MIN_POSITIVE..=f32::MAX),
+                    )
+                    .changed()
+                {
+                    update_flag.set(UpdateFlag::UPDATE_SLICE_FIELD, true);
+                }
+                ui.end_row();
+
+                ui.label("Transducer coloring:");
+                egui::ComboBox::from_id_salt("transducer_coloring")
+                    .selected_text(format!("{:?}", state.coloring))
+                    .show_ui(ui, |ui| {
+                        TransducerColoring::iter().for_each(|c| {
+                            if ui
+                                .selectable_value(&mut state.coloring, c, format!("{:?}", c))
+                                .changed()
+                            {
+                                update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
+                            }
+                        });
+                    });
+                ui.end_row();
             });
 
-        ui.label("Device index: show/enable/overheat");
+        if ui.checkbox(&mut state.tint, "Tint devices").changed() {
+            update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
+        }
+
+        ui.label("Device index: show/opacity/enable/modulation/overheat/reset overheat/force fan/GPIO in/tint");
         egui::Grid::new("config_device_grid")
             .num_columns(2)
             .min_col_width(MIN_COL_WIDTH)
@@ -794,9 +1551,22 @@ impl EguiRenderer {
                 emulator.iter_mut().enumerate().for_each(|(i, emulator)| {
                     ui.label(format!("Device {}: ", i));
                     ui.horizontal(|ui| {
-                        if ui.checkbox(emulator.visible, "").changed() {
+                        let show = ui.checkbox(emulator.visible, "").changed();
+                        let fade = ui
+                            .add(
+                                egui::DragValue::new(emulator.alpha)
+                                    .speed(0.01)
+                                    .range(0.0..=1.0),
+                            )
+                            .on_hover_text("Opacity")
+                            .changed();
+                        if show || fade {
                             update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
-                            let v = if *emulator.visible { 1. } else { 0. };
+                            let v = if *emulator.visible {
+                                *emulator.alpha
+                            } else {
+                                0.
+                            };
                             emulator.transducers.iter_mut().for_each(|s| s.alpha = v);
                         }
 
@@ -806,6 +1576,14 @@ impl EguiRenderer {
                             emulator.transducers.iter_mut().for_each(|s| s.enable = v);
                         }
 
+                        if ui
+                            .checkbox(emulator.mod_enable, "")
+                            .on_hover_text("Apply modulation (if Mod enable is on)")
+                            .changed()
+                        {
+                            update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+                        }
+
                         if ui.checkbox(emulator.thermal, "").changed() {
                             if *emulator.thermal {
                                 emulator.cpu.fpga_mut().assert_thermal_sensor();
@@ -813,10 +1591,50 @@ impl EguiRenderer {
                                 emulator.cpu.fpga_mut().deassert_thermal_sensor();
                             }
                         }
+
+                        if ui
+                            .add_enabled(*emulator.thermal, egui::Button::new("Reset").small())
+                            .on_hover_text("Deassert the thermal sensor")
+                            .clicked()
+                        {
+                            *emulator.thermal = false;
+                            emulator.cpu.fpga_mut().deassert_thermal_sensor();
+                        }
+
+                        let mut force_fan = emulator.cpu.fpga().is_force_fan();
+                        if ui.checkbox(&mut force_fan, "").changed() {
+                            emulator.cpu.fpga_mut().set_force_fan(force_fan);
+                        }
+
+                        let mut gpio_in = emulator.cpu.fpga().gpio_in();
+                        gpio_in.iter_mut().enumerate().for_each(|(pin, v)| {
+                            if ui
+                                .checkbox(v, "")
+                                .on_hover_text(format!("GPIO[{}]", pin))
+                                .changed()
+                            {
+                                emulator.cpu.fpga_mut().set_gpio_in(pin, *v);
+                            }
+                        });
+
+                        while state.tints.len() <= i {
+                            state.tints.push(default_tint(state.tints.len()));
+                        }
+                        if color_picker_color32(
+                            ui,
+                            &mut state.tints[i],
+                            egui::color_picker::Alpha::Opaque,
+                        ) && state.tint
+                        {
+                            update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
+                        }
                     });
                     ui.end_row();
                 });
             });
+        if emulator.initialized() {
+            state.devices = emulator.device_states();
+        }
 
         ui.separator();
 
@@ -834,20 +1652,268 @@ impl EguiRenderer {
                 );
                 ui.end_row();
 
+                ui.label("Font:");
+                let id = ui.id().with("font_path");
+                let mut font_path = ui
+                    .data(|d| d.get_temp::<String>(id))
+                    .unwrap_or_else(|| state.font_path.clone());
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut font_path).hint_text("default"));
+                if response.lost_focus() {
+                    state.font_path = font_path.trim().to_owned();
+                    ui.data_mut(|d| d.remove::<String>(id));
+                } else if response.has_focus() {
+                    ui.data_mut(|d| d.insert_temp(id, font_path));
+                }
+                ui.end_row();
+
+                ui.label("Font size:");
+                ui.add(
+                    DragValue::new(&mut state.font_size)
+                        .speed(0.1)
+                        .range(6.0..=48.0),
+                );
+                ui.end_row();
+
+                ui.label("Left-handed coordinates:");
+                let mut left_handed = state.left_handed;
+                if ui.checkbox(&mut left_handed, "").changed() {
+                    state.set_left_handed(left_handed);
+                    update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+                    update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
+                }
+                ui.end_row();
+
+                ui.label("Window layout:");
+                if ui.button("Reset window layout").clicked() {
+                    let path = state.layout_path();
+                    if path.exists() {
+                        if let Err(e) = std::fs::remove_file(&path) {
+                            tracing::warn!(
+                                "Failed to remove window layout ({}): {}",
+                                path.display(),
+                                e
+                            );
+                        }
+                    }
+                    ui.ctx().memory_mut(|m| m.reset_areas());
+                    state.hud.position = None;
+                }
+                ui.end_row();
+
+                ui.label("Settings:");
+                if ui.button("Save now").clicked() {
+                    state.save_request = true;
+                }
+                ui.end_row();
+
+                ui.label("Autosave interval:");
+                ui.add(
+                    DragValue::new(&mut state.autosave_interval)
+                        .range(0..=3600)
+                        .suffix(" s"),
+                )
+                .on_hover_text("0 disables autosave");
+                ui.end_row();
+
+                ui.label("HUD:");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.hud.enable, "");
+                    if state.hud.enable {
+                        ui.add(
+                            egui::Slider::new(&mut state.hud.opacity, 0.1..=1.0).text("opacity"),
+                        );
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Anaglyph (red/cyan):");
+                ui.checkbox(&mut state.anaglyph, "");
+                ui.end_row();
+
+                if state.anaglyph {
+                    ui.label("Eye separation:");
+                    if ui
+                        .add(
+                            DragValue::new(&mut state.eye_separation)
+                                .speed(1. * mm)
+                                .range(0.0..=f32::MAX),
+                        )
+                        .changed()
+                    {
+                        update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+                    }
+                    ui.end_row();
+                }
+
+                ui.label("Background mode:");
+                egui::ComboBox::from_id_salt("background_mode")
+                    .selected_text(format!("{:?}", state.background_mode))
+                    .show_ui(ui, |ui| {
+                        BackgroundMode::iter().for_each(|m| {
+                            if ui
+                                .selectable_value(&mut state.background_mode, m, format!("{:?}", m))
+                                .changed()
+                            {
+                                update_flag.set(UpdateFlag::UPDATE_BACKGROUND, true);
+                            }
+                        });
+                    });
+                ui.end_row();
+
                 ui.label("Background:");
-                color_picker_color32(ui, &mut state.background, egui::color_picker::Alpha::Opaque);
+                if color_picker_color32(
+                    ui,
+                    &mut state.background,
+                    egui::color_picker::Alpha::OnlyBlend,
+                ) {
+                    update_flag.set(UpdateFlag::UPDATE_BACKGROUND, true);
+                }
+                ui.end_row();
+
+                ui.label("Transparent background:");
+                ui.checkbox(&mut state.transparent_background, "")
+                    .on_hover_text(
+                        "Clear saved images with alpha 0. The live view is not affected.",
+                    );
+                ui.end_row();
+
+                ui.label("Capture resolution:");
+                ui.horizontal(|ui| {
+                    let mut custom = state.capture_size.is_some();
+                    if ui
+                        .checkbox(&mut custom, "")
+                        .on_hover_text("Use the window size if unchecked")
+                        .changed()
+                    {
+                        state.capture_size = custom.then_some(DEFAULT_CAPTURE_SIZE);
+                    }
+                    if let Some((width, height)) = &mut state.capture_size {
+                        ui.add(DragValue::new(width).range(1..=MAX_CAPTURE_SIZE));
+                        ui.label("x");
+                        ui.add(DragValue::new(height).range(1..=MAX_CAPTURE_SIZE));
+                    }
+                });
+                ui.end_row();
+
+                ui.label("");
+                if ui
+                    .button("Save image")
+                    .on_hover_text("Save the scene without UI as a PNG in the export dir")
+                    .clicked()
+                {
+                    state.save_image = true;
+                }
+                ui.end_row();
+
+                match state.background_mode {
+                    BackgroundMode::Solid => {}
+                    BackgroundMode::Gradient => {
+                        ui.label("Background (bottom):");
+                        if color_picker_color32(
+                            ui,
+                            &mut state.background_bottom,
+                            egui::color_picker::Alpha::Opaque,
+                        ) {
+                            update_flag.set(UpdateFlag::UPDATE_BACKGROUND, true);
+                        }
+                        ui.end_row();
+                    }
+                    BackgroundMode::Image => {
+                        ui.label("Background image (PNG):");
+                        if ui
+                            .text_edit_singleline(&mut state.background_image)
+                            .lost_focus()
+                        {
+                            update_flag.set(UpdateFlag::UPDATE_BACKGROUND, true);
+                        }
+                        ui.end_row();
+                    }
+                }
+
+                ui.label("Hide UI (F1):");
+                ui.checkbox(&mut state.hide_ui, "")
+                    .on_hover_text("Hide all windows and overlays. Press F1 to show them again.");
+                ui.end_row();
+
+                ui.label("Throttle when unfocused:");
+                ui.checkbox(&mut state.throttle_unfocused, "");
+                ui.end_row();
+
+                ui.label("VSync:");
+                if ui.checkbox(&mut state.vsync, "").changed() {
+                    update_flag.set(UpdateFlag::UPDATE_PRESENT_MODE, true);
+                }
+                ui.end_row();
+
+                ui.label("MSAA (restart required):");
+                egui::ComboBox::from_id_salt("msaa")
+                    .selected_text(format!("x{}", state.msaa.max(1)))
+                    .show_ui(ui, |ui| {
+                        [1, 2, 4, 8, 16].into_iter().for_each(|count| {
+                            ui.selectable_value(&mut state.msaa, count, format!("x{}", count));
+                        });
+                    });
             });
     }
 
+    /// Plot every sample of short buffers, and the min/max envelope of buckets for long ones so that spikes are not lost
+    fn mod_plot_points(samples: &[u8], offset: usize) -> Vec<[f64; 2]> {
+        if samples.len() <= MOD_PLOT_MAX_POINTS {
+            return samples
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| [(offset + i) as f64, v as f64])
+                .collect();
+        }
+        let bucket = samples.len().div_ceil(MOD_PLOT_MAX_POINTS / 2);
+        samples
+            .chunks(bucket)
+            .enumerate()
+            .flat_map(|(i, chunk)| {
+                let x = (offset + i * bucket) as f64 + (chunk.len() - 1) as f64 / 2.;
+                let min = chunk.iter().copied().min().unwrap_or_default();
+                let max = chunk.iter().copied().max().unwrap_or_default();
+                [[x, min as f64], [x, max as f64]]
+            })
+            .collect()
+    }
+
     fn info_tab(
         ui: &mut egui::Ui,
         state: &mut crate::State,
         emulator: &mut EmulatorWrapper,
         update_flag: &mut UpdateFlag,
     ) {
+        ui.checkbox(&mut state.raw_units, "Raw units");
+        let raw = state.raw_units;
+        ui.horizontal(|ui| {
+            ui.label("Export dir:");
+            ui.text_edit_singleline(&mut state.export_dir);
+        });
+        let export_dir = std::path::PathBuf::from(&state.export_dir);
+
+        if ui.button("Export geometry").clicked() {
+            let path = export_dir.join("geometry.json");
+            match save_geometry(&path, emulator) {
+                Ok(()) => tracing::info!("Geometry is exported to {}", path.display()),
+                Err(e) => tracing::error!("Failed to export geometry to {}: {}", path.display(), e),
+            }
+        }
+
+        let selected = state.selection.device;
+        let scroll = std::mem::take(&mut state.selection.scroll);
         emulator.iter_mut().for_each(|emulator| {
             let cpu = emulator.cpu;
-            ui.collapsing(format!("Device {}", cpu.idx()), |ui| {
+            let reveal = scroll && selected == Some(cpu.idx());
+            let mut header = egui::RichText::new(format!("Device {}", cpu.idx()));
+            if selected == Some(cpu.idx()) {
+                header = header.color(egui::Color32::ORANGE);
+            }
+            let header = egui::CollapsingHeader::new(header)
+                .id_salt(("device", cpu.idx()))
+                .open(reveal.then_some(true));
+            let response = header.show(ui, |ui| {
                 ui.collapsing("Silencer", |ui| {
                     if cpu.fpga().silencer_fixed_completion_steps_mode() {
                         ui.label(format!(
@@ -883,12 +1949,18 @@ impl EguiRenderer {
                     ));
                     let sampling_freq = ULTRASOUND_FREQ.hz() as f32
                         / cpu.fpga().modulation_freq_division(segment) as f32;
-                    ui.label(format!("Sampling Frequency: {:.3}Hz", sampling_freq));
+                    ui.label(format!(
+                        "Sampling Frequency: {}",
+                        format_freq(sampling_freq, raw)
+                    ));
                     let sampling_period =
                         ULTRASOUND_PERIOD * cpu.fpga().modulation_freq_division(segment) as u32;
-                    ui.label(format!("Sampling period: {:?}", sampling_period));
+                    ui.label(format!(
+                        "Sampling period: {}",
+                        format_duration(sampling_period, raw)
+                    ));
                     let period = sampling_period * mod_size as u32;
-                    ui.label(format!("Period: {:?}", period));
+                    ui.label(format!("Period: {}", format_duration(period, raw)));
 
                     ui.label(format!("Current Index: {}", cpu.fpga().current_mod_idx()));
 
@@ -904,7 +1976,52 @@ impl EguiRenderer {
                         ui.label(format!("mod[{}]: {}", mod_size - 1, m[mod_size - 1]));
                     }
 
+                    if ui.button("Export modulation").clicked() {
+                        let path =
+                            export_dir.join(format!("modulation_{}_{:?}.csv", cpu.idx(), segment));
+                        match write_modulation_csv(
+                            &path,
+                            segment,
+                            cpu.fpga().modulation_freq_division(segment),
+                            &m,
+                        ) {
+                            Ok(()) => {
+                                tracing::info!("Modulation is exported to {}", path.display())
+                            }
+                            Err(e) => tracing::error!(
+                                "Failed to export modulation to {}: {}",
+                                path.display(),
+                                e
+                            ),
+                        }
+                    }
+
                     ui.collapsing("Plot", |ui| {
+                        let id = egui::Id::new(("mod_plot_window", cpu.idx()));
+                        let (mut window, mut offset) = ui.data_mut(|d| {
+                            *d.get_persisted_mut_or(id, (MOD_PLOT_DEFAULT_WINDOW, 0usize))
+                        });
+                        window = window.clamp(1, mod_size.max(1));
+                        ui.horizontal(|ui| {
+                            ui.label("Window:");
+                            ui.add(DragValue::new(&mut window).range(1..=mod_size.max(1)));
+                            ui.label("Offset:");
+                            ui.add(
+                                DragValue::new(&mut offset)
+                                    .range(0..=mod_size.saturating_sub(window)),
+                            );
+                        });
+                        offset = offset.min(mod_size.saturating_sub(window));
+                        ui.data_mut(|d| d.insert_persisted(id, (window, offset)));
+
+                        let samples = &m[offset.min(mod_size)..(offset + window).min(mod_size)];
+                        if samples.len() > MOD_PLOT_MAX_POINTS {
+                            ui.label(format!(
+                                "Showing min/max of {} samples per point",
+                                samples.len().div_ceil(MOD_PLOT_MAX_POINTS / 2)
+                            ));
+                        }
+                        let points = Self::mod_plot_points(samples, offset);
                         egui_plot::Plot::new("plot")
                             .x_axis_label("Index")
                             .y_grid_spacer(|_g| {
@@ -922,9 +2039,7 @@ impl EguiRenderer {
                             .width(ui.max_rect().width() * 0.8)
                             .height(200.)
                             .show(ui, |plot_ui| {
-                                plot_ui.line(Line::new(PlotPoints::from_iter(
-                                    m.into_iter().enumerate().map(|(i, v)| [i as f64, v as _]),
-                                )));
+                                plot_ui.line(Line::new(PlotPoints::new(points)));
                             });
                     });
                 });
@@ -970,12 +2085,18 @@ impl EguiRenderer {
                         ));
                         let sampling_freq = ULTRASOUND_FREQ.hz() as f32
                             / cpu.fpga().stm_freq_division(segment) as f32;
-                        ui.label(format!("Sampling Frequency: {:.3}Hz", sampling_freq));
+                        ui.label(format!(
+                            "Sampling Frequency: {}",
+                            format_freq(sampling_freq, raw)
+                        ));
                         let sampling_period =
                             ULTRASOUND_PERIOD * cpu.fpga().stm_freq_division(segment) as u32;
-                        ui.label(format!("Sampling period: {:?}", sampling_period));
+                        ui.label(format!(
+                            "Sampling period: {}",
+                            format_duration(sampling_period, raw)
+                        ));
                         let period = sampling_period * stm_size as u32;
-                        ui.label(format!("Period: {:?}", period));
+                        ui.label(format!("Period: {}", format_duration(period, raw)));
 
                         ui.label(format!("Current Index: {}", cpu.fpga().current_stm_idx()));
                     }
@@ -1069,29 +2190,10 @@ impl EguiRenderer {
                                 cpu.fpga().current_mod_segment(),
                                 cpu.fpga().current_mod_idx(),
                             );
-                            let phase = d.phase().value() as u32;
-                            let pulse_width = cpu.fpga().to_pulse_width(d.intensity(), m) as u32;
-                            const T: u32 = ULTRASOUND_PERIOD_COUNT as u32;
-                            let rise = (phase + T - pulse_width / 2) % T;
-                            let fall = (phase + (pulse_width + 1) / 2) % T;
-                            #[allow(clippy::collapsible_else_if)]
-                            (0..T)
-                                .map(|t| {
-                                    if rise <= fall {
-                                        if (rise <= t) && (t < fall) {
-                                            1.0
-                                        } else {
-                                            0.0
-                                        }
-                                    } else {
-                                        if (t < fall) || (rise <= t) {
-                                            1.0
-                                        } else {
-                                            0.0
-                                        }
-                                    }
-                                })
-                                .collect()
+                            pwm_out(
+                                d.phase().value() as u32,
+                                cpu.fpga().to_pulse_width(d.intensity(), m) as u32,
+                            )
                         }
                         autd3_firmware_emulator::fpga::params::DBG_SYS_TIME_EQ => {
                             let now = (((cpu.dc_sys_time().sys_time() / 25000) << 8)
@@ -1136,6 +2238,11 @@ impl EguiRenderer {
                     });
                 });
             });
+            if reveal {
+                response
+                    .header_response
+                    .scroll_to_me(Some(egui::Align::TOP));
+            }
         });
 
         ui.separator();
@@ -1173,23 +2280,110 @@ impl EguiRenderer {
                                 state.real_time.wrapping_add_signed(state.time_step as _);
                             update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
                         }
-                        ui.add(
-                            DragValue::new(&mut state.time_step)
-                                .speed(1000)
-                                .range(1..=i32::MAX),
-                        );
+                        let scale = state.time_step_unit.scale();
+                        let mut step = state.time_step as f64 / scale as f64;
+                        if ui
+                            .add(
+                                DragValue::new(&mut step)
+                                    .speed(1)
+                                    .range(1. / scale as f64..=(i32::MAX / scale) as f64),
+                            )
+                            .changed()
+                        {
+                            state.time_step = ((step * scale as f64) as i32).max(1);
+                        }
+                        egui::ComboBox::from_id_salt("time_step_unit")
+                            .selected_text(state.time_step_unit.suffix())
+                            .show_ui(ui, |ui| {
+                                TimeUnit::iter().for_each(|u| {
+                                    ui.selectable_value(&mut state.time_step_unit, u, u.suffix());
+                                });
+                            });
                     });
                 }
                 ui.end_row();
+
+                if !state.auto_play {
+                    let mod_sampling_period = emulator.iter_mut().next().map(|emulator| {
+                        let fpga = emulator.cpu.fpga();
+                        ULTRASOUND_PERIOD
+                            * fpga.modulation_freq_division(fpga.current_mod_segment()) as u32
+                    });
+
+                    ui.label("Step:");
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("1 period")
+                            .on_hover_text("Advance by one ultrasound period")
+                            .clicked()
+                        {
+                            state.real_time = state
+                                .real_time
+                                .wrapping_add(ULTRASOUND_PERIOD.as_nanos() as u64);
+                            update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+                        }
+                        if let Some(period) = mod_sampling_period {
+                            if ui
+                                .button("1 mod sample")
+                                .on_hover_text(
+                                    "Advance by one modulation sampling period of device 0",
+                                )
+                                .clicked()
+                            {
+                                state.real_time =
+                                    state.real_time.wrapping_add(period.as_nanos() as u64);
+                                update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    emulator.iter_mut().for_each(|emulator| {
+                        let fpga = emulator.cpu.fpga();
+                        ui.label(format!("Device {}:", emulator.cpu.idx()));
+                        ui.label(format!(
+                            "mod {:?}[{}], STM {:?}[{}]",
+                            fpga.current_mod_segment(),
+                            fpga.current_mod_idx(),
+                            fpga.current_stm_segment(),
+                            fpga.current_stm_idx()
+                        ));
+                        ui.end_row();
+                    });
+                }
             });
     }
 
-    pub(crate) fn _waiting(&self, ctx: &egui::Context) {
+    pub(crate) fn _waiting(&self, ctx: &egui::Context, state: &crate::State) {
+        let elapsed = self
+            .waiting_since
+            .map(|t| t.elapsed().as_secs())
+            .unwrap_or_default();
         egui::Window::new("Control panel")
             .resizable(true)
             .vscroll(true)
             .default_open(true)
-            .show(ctx, |ui| ui.label("Waiting for client connection..."));
+            .show(ctx, |ui| {
+                ui.label("Waiting for client connection...");
+                egui::Grid::new("waiting_grid")
+                    .num_columns(2)
+                    .min_col_width(MIN_COL_WIDTH)
+                    .spacing(SPACING)
+                    .show(ui, |ui| {
+                        ui.label("Address:");
+                        ui.label(state.address());
+                        ui.end_row();
+
+                        ui.label("Lightweight mode:");
+                        ui.label(if state.lightweight { "on" } else { "off" });
+                        ui.end_row();
+
+                        ui.label("Elapsed:");
+                        ui.label(format!("{} s", elapsed));
+                        ui.end_row();
+                    });
+            });
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
     }
 
     pub fn on_window_event(
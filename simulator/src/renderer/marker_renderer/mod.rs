@@ -0,0 +1,156 @@
+use autd3_driver::defined::mm;
+use egui_wgpu::wgpu;
+use std::borrow::Cow;
+use wgpu::{util::DeviceExt, Device, Queue, RenderPass, SurfaceConfiguration};
+
+use crate::{common::transform::to_gl_pos, Matrix4, State, Vector3, Vector4};
+
+use super::DepthTexture;
+
+const MARKER_SIZE: f32 = 5. * mm;
+
+pub struct MarkerRenderer {
+    vertex_buf: Option<wgpu::Buffer>,
+    vertex_count: u32,
+    proj_view_buf: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl MarkerRenderer {
+    pub fn new(device: &Device, surface_config: &SurfaceConfiguration, sample_count: u32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(64),
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let proj_view_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Marker Projection View Buffer"),
+            size: size_of::<Matrix4>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: proj_view_buf.as_entire_binding(),
+            }],
+            label: None,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<Vector4>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32x4,
+                        offset: 0,
+                        shader_location: 0,
+                    }],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.view_formats[0],
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // markers are drawn on top so that they are not hidden behind the slice
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            vertex_buf: None,
+            vertex_count: 0,
+            proj_view_buf,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn update_markers(&mut self, state: &State, device: &Device) {
+        let vertices = state
+            .markers
+            .iter()
+            .flat_map(|&p| {
+                [Vector3::X, Vector3::Y, Vector3::Z]
+                    .into_iter()
+                    .flat_map(move |axis| [p - axis * MARKER_SIZE, p + axis * MARKER_SIZE])
+            })
+            .map(|p| to_gl_pos(p).extend(1.))
+            .collect::<Vec<_>>();
+        self.vertex_count = vertices.len() as u32;
+        self.vertex_buf = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Marker Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+    }
+
+    pub fn update_camera(&mut self, proj_view: Matrix4, queue: &Queue) {
+        queue.write_buffer(
+            &self.proj_view_buf,
+            0,
+            bytemuck::cast_slice(proj_view.as_ref()),
+        );
+    }
+
+    pub fn render(&self, pass: &mut RenderPass) {
+        let Some(vertex_buf) = &self.vertex_buf else {
+            return;
+        };
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buf.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,13 @@
+@group(0)
+@binding(0)
+var<uniform> proj_view: mat4x4<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec4<f32>) -> @builtin(position) vec4<f32> {
+    return proj_view * position;
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+}
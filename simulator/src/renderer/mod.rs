@@ -1,34 +1,66 @@
+mod background_renderer;
+mod benchmark;
+mod capture;
 mod depth_texture;
 mod egui_renderer;
+mod marker_renderer;
+mod msaa_texture;
 mod slice_renderer;
 mod transducer_renderer;
 
-use std::{num::NonZeroU32, sync::Arc};
+use autd3_driver::defined::METER;
+
+use std::{
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
     common::camera::{create_camera, Camera, CameraPerspective},
     emulator::EmulatorWrapper,
     error::{Result, SimulatorError},
     event::{EventResult, UserEvent},
+    state::BackgroundMode,
     update_flag::UpdateFlag,
     Matrix4, State, Vector3,
 };
 
+pub use benchmark::{benchmark, BenchmarkResult};
 use depth_texture::DepthTexture;
 use egui::ViewportId;
 use egui_renderer::EguiRenderer;
 use egui_wgpu::ScreenDescriptor;
+use msaa_texture::MsaaTexture;
 use winit::{event::DeviceEvent, event_loop::EventLoopProxy, window::Window};
 
+pub fn available_gpus() -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        ..Default::default()
+    });
+    instance
+        .enumerate_adapters(wgpu::Backends::PRIMARY)
+        .iter()
+        .map(wgpu::Adapter::get_info)
+        .collect()
+}
+
 pub struct Renderer {
     surface: wgpu::Surface<'static>,
     surface_config: wgpu::SurfaceConfiguration,
     device: wgpu::Device,
     queue: wgpu::Queue,
     egui_renderer: egui_renderer::EguiRenderer,
+    background_renderer: background_renderer::BackgroundRenderer,
     transducer_renderer: transducer_renderer::TransducerRenderer,
     slice_renderer: slice_renderer::SliceRenderer,
+    marker_renderer: marker_renderer::MarkerRenderer,
     depth_texture: DepthTexture,
+    msaa_texture: Option<MsaaTexture>,
+    sample_count: u32,
+    present_modes: Vec<wgpu::PresentMode>,
     camera: Camera<f32>,
 }
 
@@ -77,11 +109,7 @@ impl Renderer {
             format: *swapchain_format,
             width,
             height,
-            present_mode: if state.vsync {
-                wgpu::PresentMode::AutoVsync
-            } else {
-                wgpu::PresentMode::AutoNoVsync
-            },
+            present_mode: Self::present_mode(state.vsync, &swapchain_capabilities.present_modes),
             desired_maximum_frame_latency: 0,
             alpha_mode: swapchain_capabilities.alpha_modes[0],
             view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
@@ -89,6 +117,19 @@ impl Renderer {
 
         surface.configure(&device, &surface_config);
 
+        let sample_count = Self::max_sample_count(
+            &adapter,
+            &[surface_config.view_formats[0], DepthTexture::DEPTH_FORMAT],
+            state.msaa.max(1),
+        );
+        if sample_count != state.msaa.max(1) {
+            tracing::warn!(
+                "MSAA x{} is not supported, using x{} instead",
+                state.msaa.max(1),
+                sample_count
+            );
+        }
+
         Ok(Self {
             egui_renderer: EguiRenderer::new(
                 state,
@@ -98,13 +139,34 @@ impl Renderer {
                 window,
                 &surface_config,
             ),
+            background_renderer: background_renderer::BackgroundRenderer::new(
+                &device,
+                &queue,
+                &surface_config,
+                sample_count,
+                state,
+            ),
             transducer_renderer: transducer_renderer::TransducerRenderer::new(
                 &device,
                 &queue,
                 &surface_config,
+                sample_count,
             )?,
-            slice_renderer: slice_renderer::SliceRenderer::new(&device, &surface_config),
-            depth_texture: DepthTexture::new(&device, &surface_config),
+            slice_renderer: slice_renderer::SliceRenderer::new(
+                &device,
+                &surface_config,
+                sample_count,
+            ),
+            marker_renderer: marker_renderer::MarkerRenderer::new(
+                &device,
+                &surface_config,
+                sample_count,
+            ),
+            depth_texture: DepthTexture::new(&device, &surface_config, sample_count),
+            msaa_texture: (sample_count > 1)
+                .then(|| MsaaTexture::new(&device, &surface_config, sample_count)),
+            sample_count,
+            present_modes: swapchain_capabilities.present_modes,
             camera: create_camera(),
             surface,
             surface_config,
@@ -113,6 +175,56 @@ impl Renderer {
         })
     }
 
+    fn present_mode(vsync: bool, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let candidates: &[wgpu::PresentMode] = if vsync {
+            &[wgpu::PresentMode::Fifo]
+        } else {
+            &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+        };
+        candidates
+            .iter()
+            .find(|mode| supported.contains(mode))
+            .copied()
+            .unwrap_or_else(|| {
+                tracing::warn!(
+                    "{:?} is not supported by the surface, falling back to Fifo",
+                    candidates
+                );
+                wgpu::PresentMode::Fifo
+            })
+    }
+
+    pub fn update_present_mode(&mut self, state: &State) {
+        let present_mode = Self::present_mode(state.vsync, &self.present_modes);
+        if self.surface_config.present_mode != present_mode {
+            self.surface_config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    fn max_sample_count(
+        adapter: &wgpu::Adapter,
+        formats: &[wgpu::TextureFormat],
+        requested: u32,
+    ) -> u32 {
+        [16, 8, 4, 2, 1]
+            .into_iter()
+            .filter(|&count| count <= requested)
+            .find(|&count| {
+                formats.iter().all(|&format| {
+                    adapter
+                        .get_texture_format_features(format)
+                        .flags
+                        .sample_count_supported(count)
+                })
+            })
+            .unwrap_or(1)
+    }
+
+    pub fn save_layout(&self, state: &mut State) {
+        self.egui_renderer.save_layout(state);
+    }
+
     pub fn create_egui_context() -> egui::Context {
         EguiRenderer::create_egui_context()
     }
@@ -129,14 +241,17 @@ impl Renderer {
         window: &Window,
         update_flag: &mut UpdateFlag,
     ) -> Result<EventResult> {
+        let view_proj = Self::proj_view(&self.camera, state, window);
         let Self {
             surface,
             surface_config,
             device,
             queue,
             egui_renderer,
+            background_renderer,
             transducer_renderer,
             slice_renderer,
+            marker_renderer,
             ..
         } = self;
 
@@ -154,38 +269,47 @@ impl Renderer {
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        let load = if emulator.initialized() {
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: None,
-                    timestamp_writes: None,
-                });
-                slice_renderer.compute(&mut compute_pass);
-            }
+        if emulator.initialized() {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            slice_renderer.compute(&mut compute_pass);
+            drop(compute_pass);
+            slice_renderer.copy_snapshot(&mut encoder);
+        }
 
+        let draw_background = state.background_mode != BackgroundMode::Solid;
+        let anaglyph = state.anaglyph && emulator.initialized();
+        let load = if emulator.initialized() || draw_background {
             {
-                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("main render pass"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &surface_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(state.background()),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: self.depth_texture.view(),
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
-                            store: wgpu::StoreOp::Store,
-                        }),
-                        stencil_ops: None,
-                    }),
-                    timestamp_writes: None,
-                    occlusion_query_set: None,
-                });
-                transducer_renderer.render(&mut rpass);
+                let mut rpass = Self::begin_scene_pass(
+                    &mut encoder,
+                    &surface_view,
+                    self.msaa_texture.as_ref(),
+                    &self.depth_texture,
+                    wgpu::LoadOp::Clear(state.background()),
+                );
+                if draw_background {
+                    background_renderer.render(&mut rpass);
+                }
+                if anaglyph {
+                    transducer_renderer.render_eye(&mut rpass, 0);
+                } else if emulator.initialized() {
+                    transducer_renderer.render(&mut rpass);
+                    slice_renderer.render(&mut rpass);
+                    marker_renderer.render(&mut rpass);
+                }
+            }
+            if anaglyph {
+                let mut rpass = Self::begin_scene_pass(
+                    &mut encoder,
+                    &surface_view,
+                    self.msaa_texture.as_ref(),
+                    &self.depth_texture,
+                    wgpu::LoadOp::Load,
+                );
+                transducer_renderer.render_eye(&mut rpass, 1);
                 slice_renderer.render(&mut rpass);
             }
             wgpu::LoadOp::Load
@@ -201,6 +325,7 @@ impl Renderer {
             &surface_view,
             screen_descriptor,
             window,
+            view_proj,
             state,
             emulator,
             update_flag,
@@ -212,6 +337,156 @@ impl Renderer {
         Ok(result)
     }
 
+    /// Render the scene without UI into an offscreen texture and save it as a PNG in the export directory.
+    ///
+    /// The swapchain is composited as opaque on most platforms, so the capture is rendered into a separate `Bgra8UnormSrgb` texture (the surface view format) with `COPY_SRC` usage, which keeps the alpha of the clear color.
+    /// The texture has the capture resolution if set, and the window size otherwise. The vertical FOV is kept and the projection uses the capture aspect ratio, so the scene is not stretched.
+    pub fn save_image(
+        &mut self,
+        state: &State,
+        emulator: &EmulatorWrapper,
+        window: &Window,
+    ) -> Result<PathBuf> {
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        let mut config = self.surface_config.clone();
+        if let Some((width, height)) = state.capture_size {
+            config.width = width.clamp(1, max_size);
+            config.height = height.clamp(1, max_size);
+        }
+
+        let view_proj = Self::offset_proj_view(
+            &self.camera,
+            state,
+            config.width as f32 / config.height as f32,
+            0.,
+        );
+        self.transducer_renderer
+            .update_camera(view_proj, &self.queue);
+        self.slice_renderer.update_camera(view_proj, &self.queue);
+        self.marker_renderer.update_camera(view_proj, &self.queue);
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.view_formats[0],
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = DepthTexture::new(&self.device, &config, self.sample_count);
+        let msaa_texture = (self.sample_count > 1)
+            .then(|| MsaaTexture::new(&self.device, &config, self.sample_count));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut rpass = Self::begin_scene_pass(
+                &mut encoder,
+                &view,
+                msaa_texture.as_ref(),
+                &depth_texture,
+                wgpu::LoadOp::Clear(state.capture_background()),
+            );
+            if !state.transparent_background && state.background_mode != BackgroundMode::Solid {
+                self.background_renderer.render(&mut rpass);
+            }
+            if emulator.initialized() {
+                self.transducer_renderer.render(&mut rpass);
+                self.slice_renderer.render(&mut rpass);
+                self.marker_renderer.render(&mut rpass);
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        let view_proj = Self::proj_view(&self.camera, state, window);
+        self.transducer_renderer
+            .update_camera(view_proj, &self.queue);
+        self.slice_renderer.update_camera(view_proj, &self.queue);
+        self.marker_renderer.update_camera(view_proj, &self.queue);
+
+        let image = capture::read_texture(
+            &self.device,
+            &self.queue,
+            &texture,
+            config.width,
+            config.height,
+        )?;
+
+        let export_dir = PathBuf::from(&state.export_dir);
+        if !state.export_dir.is_empty() {
+            std::fs::create_dir_all(&export_dir)?;
+        }
+        let path = export_dir.join(format!(
+            "screenshot_{}.png",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        ));
+        capture::save_png(
+            &path,
+            &image,
+            &[
+                (
+                    "Software",
+                    format!("AUTD3 Simulator v{}", env!("CARGO_PKG_VERSION")),
+                ),
+                ("ColorMap", format!("{:?}", state.slice.color_map)),
+                ("Field", format!("{:?}", state.slice.field)),
+                ("PressureMax", format!("{} Pa", state.slice.pressure_max)),
+                (
+                    "IntensityMax",
+                    format!("{} W/m^2", state.slice.intensity_max),
+                ),
+                ("SoundSpeed", format!("{} m/s", state.sound_speed / METER)),
+                ("Devices", emulator.num_devices().to_string()),
+                (
+                    "SystemTime",
+                    format!("{} ns", state.system_time().sys_time()),
+                ),
+            ],
+        )?;
+        Ok(path)
+    }
+
+    fn begin_scene_pass<'a>(
+        encoder: &'a mut wgpu::CommandEncoder,
+        surface_view: &wgpu::TextureView,
+        msaa_texture: Option<&MsaaTexture>,
+        depth_texture: &DepthTexture,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("main render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_texture.map_or(surface_view, |msaa| msaa.view()),
+                resolve_target: msaa_texture.map(|_| surface_view),
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_texture.view(),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
     pub fn update_camera(&mut self, state: &State, window: &Window) {
         crate::common::camera::set_camera(
             &mut self.camera,
@@ -221,18 +496,45 @@ impl Renderer {
         let view_proj = Self::proj_view(&self.camera, state, window);
         self.transducer_renderer
             .update_camera(view_proj, &self.queue);
+        self.transducer_renderer.update_eye_cameras(
+            Self::eye_proj_views(&self.camera, state, window),
+            &self.queue,
+        );
         self.slice_renderer.update_camera(view_proj, &self.queue);
+        self.marker_renderer.update_camera(view_proj, &self.queue);
     }
 
     fn proj_view(camera: &Camera<f32>, state: &State, window: &Window) -> Matrix4 {
-        fn projection(state: &State, window: &Window) -> Matrix4 {
-            let draw_size = window.inner_size();
+        Self::offset_proj_view(camera, state, Self::aspect_ratio(window), 0.)
+    }
+
+    fn eye_proj_views(camera: &Camera<f32>, state: &State, window: &Window) -> [Matrix4; 2] {
+        let d = state.eye_separation / 2.;
+        let aspect_ratio = Self::aspect_ratio(window);
+        [
+            Self::offset_proj_view(camera, state, aspect_ratio, -d),
+            Self::offset_proj_view(camera, state, aspect_ratio, d),
+        ]
+    }
+
+    fn aspect_ratio(window: &Window) -> f32 {
+        let draw_size = window.inner_size();
+        (draw_size.width as f32) / (draw_size.height as f32)
+    }
+
+    fn offset_proj_view(
+        camera: &Camera<f32>,
+        state: &State,
+        aspect_ratio: f32,
+        eye_offset: f32,
+    ) -> Matrix4 {
+        fn projection(state: &State, aspect_ratio: f32) -> Matrix4 {
             Matrix4::from_cols_array_2d(
                 &CameraPerspective {
                     fov: state.camera.fov,
                     near_clip: state.camera.near_clip,
                     far_clip: state.camera.far_clip,
-                    aspect_ratio: (draw_size.width as f32) / (draw_size.height as f32),
+                    aspect_ratio,
                 }
                 .projection(),
             )
@@ -242,7 +544,10 @@ impl Renderer {
             Matrix4::from_cols_array_2d(&camera.orthogonal())
         }
 
-        projection(state, window) * view(camera)
+        projection(state, aspect_ratio)
+            * Matrix4::from_translation(Vector3::new(-eye_offset, 0., 0.))
+            * view(camera)
+            * Matrix4::from_quat(state.up_axis.rotation())
     }
 
     pub fn update_trans_pos(&mut self, emulator: &EmulatorWrapper) {
@@ -255,8 +560,9 @@ impl Renderer {
             .update_trans_state(emulator, &self.queue);
     }
 
-    pub fn update_color(&mut self, emulator: &EmulatorWrapper) {
-        self.transducer_renderer.update_color(emulator, &self.queue);
+    pub fn update_color(&mut self, emulator: &EmulatorWrapper, state: &State) {
+        self.transducer_renderer
+            .update_color(emulator, state, &self.queue);
     }
 
     pub fn update_slice(&mut self, state: &State) {
@@ -272,6 +578,25 @@ impl Renderer {
         self.slice_renderer.update_color_map(state, &self.queue);
     }
 
+    pub fn update_markers(&mut self, state: &State) {
+        self.marker_renderer.update_markers(state, &self.device);
+    }
+
+    pub fn take_snapshot(&mut self) {
+        self.slice_renderer.take_snapshot();
+    }
+
+    pub fn update_background(&mut self, state: &State) {
+        self.background_renderer
+            .update(&self.device, &self.queue, state);
+    }
+
+    pub fn compute_at(&self, points: &[Vector3]) -> Result<Vec<f32>> {
+        Ok(self
+            .slice_renderer
+            .compute_at(&self.device, &self.queue, points)?)
+    }
+
     pub(crate) fn on_window_event(
         &mut self,
         event: &winit::event::WindowEvent,
@@ -302,8 +627,16 @@ impl Renderer {
 
                     let view_proj = Self::proj_view(camera, state, window);
                     self.transducer_renderer.resize(view_proj, queue);
+                    self.transducer_renderer
+                        .update_eye_cameras(Self::eye_proj_views(camera, state, window), queue);
                     self.slice_renderer.resize(view_proj, queue);
-                    self.depth_texture = DepthTexture::new(device, surface_config);
+                    self.marker_renderer.update_camera(view_proj, queue);
+                    self.depth_texture =
+                        DepthTexture::new(device, surface_config, self.sample_count);
+                    if self.msaa_texture.is_some() {
+                        self.msaa_texture =
+                            Some(MsaaTexture::new(device, surface_config, self.sample_count));
+                    }
                 }
             }
 
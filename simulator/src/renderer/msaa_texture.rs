@@ -0,0 +1,34 @@
+use wgpu::{Device, SurfaceConfiguration};
+
+pub struct MsaaTexture {
+    view: wgpu::TextureView,
+}
+
+impl MsaaTexture {
+    pub fn new(device: &Device, surface_config: &SurfaceConfiguration, sample_count: u32) -> Self {
+        let size = wgpu::Extent3d {
+            width: surface_config.width.max(1),
+            height: surface_config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: surface_config.view_formats[0],
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { view }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
@@ -1,21 +1,28 @@
-use autd3_driver::defined::mm;
+use autd3_driver::defined::{mm, METER};
 use bytemuck::{NoUninit, Pod, Zeroable};
 use egui_wgpu::wgpu;
 use std::{borrow::Cow, mem};
-use wgpu::{util::DeviceExt, ComputePass, Device, Queue, RenderPass, SurfaceConfiguration};
+use wgpu::{
+    util::DeviceExt, CommandEncoder, ComputePass, Device, Queue, RenderPass, SurfaceConfiguration,
+};
 
 use crate::{
-    common::transform::{to_gl_pos, to_gl_rot},
+    common::{
+        color_map::ColorMap,
+        transform::{to_gl_pos, to_gl_rot},
+    },
     emulator::EmulatorWrapper,
-    state::State,
+    state::{SliceField, SliceState, State},
     Matrix4, Vector2, Vector3, Vector4,
 };
 
 use super::DepthTexture;
 
-const TEXTURE_DIMS: (u32, u32) = (1024, 1024);
+const TEXTURE_DIMS: (u32, u32) = (SliceState::MAX_RESOLUTION, SliceState::MAX_RESOLUTION);
 const WORKGROUP_SIZE: (u32, u32) = (8, 8);
 const COLOR_MAP_TEXTURE_SIZE: u32 = 256;
+const POINTS_WORKGROUP_SIZE: u32 = 64;
+const CARPET_SIZE: u32 = 256;
 
 #[derive(NoUninit, Clone, Copy)]
 #[repr(C)]
@@ -24,6 +31,13 @@ struct Config {
     num_trans: u32,
     max_pressure: f32,
     scale: f32,
+    field: u32,
+    max_intensity: f32,
+    impedance: f32,
+    mask_threshold: f32,
+    carpet_height: f32,
+    diff: u32,
+    _pad: [f32; 2],
 }
 
 pub struct SliceRenderer {
@@ -37,11 +51,24 @@ pub struct SliceRenderer {
     config_buf: Option<wgpu::Buffer>,
     texture_view: wgpu::TextureView,
     color_map_texture: wgpu::Texture,
+    diff_color_map_texture: wgpu::Texture,
+    field_buf: wgpu::Buffer,
+    snapshot_buf: wgpu::Buffer,
+    snapshot_pending: bool,
+    resolution: (u32, u32),
     index_count: usize,
     bind_group: Option<wgpu::BindGroup>,
     bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::RenderPipeline,
     compute_pipeline: wgpu::ComputePipeline,
+    points_bind_group_layout: wgpu::BindGroupLayout,
+    points_pipeline: wgpu::ComputePipeline,
+    carpet: bool,
+    carpet_vertex_buf: wgpu::Buffer,
+    carpet_index_buf: wgpu::Buffer,
+    carpet_index_count: usize,
+    carpet_bind_group: wgpu::BindGroup,
+    carpet_pipeline: wgpu::ComputePipeline,
 }
 
 #[repr(C)]
@@ -49,12 +76,14 @@ pub struct SliceRenderer {
 struct Vertex {
     _pos: [f32; 4],
     _tex_coord: [f32; 2],
+    _pad: [f32; 2],
 }
 
 fn vertex(pos: [f32; 3], tc: [i8; 2]) -> Vertex {
     Vertex {
         _pos: [pos[0], pos[1], pos[2], 1.0],
         _tex_coord: [tc[0] as f32, tc[1] as f32],
+        _pad: [0.; 2],
     }
 }
 
@@ -71,8 +100,42 @@ fn create_vertices() -> (Vec<Vertex>, Vec<u16>) {
     (vertex_data.to_vec(), index_data.to_vec())
 }
 
+fn color_map_texels(color_map: ColorMap) -> Vec<u8> {
+    let iter = (0..COLOR_MAP_TEXTURE_SIZE).map(|x| x as f64 / COLOR_MAP_TEXTURE_SIZE as f64);
+    color_map
+        .color_map(iter)
+        .into_iter()
+        .flat_map(|color| {
+            [
+                (color.r * 255.) as u8,
+                (color.g * 255.) as u8,
+                (color.b * 255.) as u8,
+                255,
+            ]
+        })
+        .collect()
+}
+
+fn create_carpet_indices() -> Vec<u32> {
+    (0..CARPET_SIZE - 1)
+        .flat_map(|y| {
+            (0..CARPET_SIZE - 1).flat_map(move |x| {
+                let i = y * CARPET_SIZE + x;
+                [
+                    i,
+                    i + CARPET_SIZE + 1,
+                    i + 1,
+                    i,
+                    i + CARPET_SIZE,
+                    i + CARPET_SIZE + 1,
+                ]
+            })
+        })
+        .collect()
+}
+
 impl SliceRenderer {
-    pub fn new(device: &Device, surface_config: &SurfaceConfiguration) -> Self {
+    pub fn new(device: &Device, surface_config: &SurfaceConfiguration, sample_count: u32) -> Self {
         let vertex_size = mem::size_of::<Vertex>();
         let (vertex_data, index_data) = create_vertices();
 
@@ -128,6 +191,30 @@ impl SliceRenderer {
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
+        let diff_color_map_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: texture_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D1,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let field_size = (TEXTURE_DIMS.0 * TEXTURE_DIMS.1) as usize * size_of::<f32>();
+        let field_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Slice Field Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            size: field_size as _,
+            mapped_at_creation: false,
+        });
+        let snapshot_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Slice Snapshot Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            size: field_size as _,
+            mapped_at_creation: false,
+        });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
@@ -198,7 +285,7 @@ impl SliceRenderer {
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new(16),
+                        min_binding_size: wgpu::BufferSize::new(size_of::<Config>() as _),
                     },
                     count: None,
                 },
@@ -212,6 +299,36 @@ impl SliceRenderer {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D1,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -292,7 +409,10 @@ impl SliceRenderer {
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             multiview: None,
             cache: None,
         });
@@ -307,6 +427,130 @@ impl SliceRenderer {
             label: None,
             layout: Some(&compute_pipeline_layout),
             module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let carpet_vertex_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Slice Carpet Vertex Buffer"),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            size: ((CARPET_SIZE * CARPET_SIZE) as usize * vertex_size) as _,
+            mapped_at_creation: false,
+        });
+        let carpet_index_data = create_carpet_indices();
+        let carpet_index_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Slice Carpet Index Buffer"),
+            usage: wgpu::BufferUsages::INDEX,
+            contents: bytemuck::cast_slice(&carpet_index_data),
+        });
+        let carpet_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let carpet_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &carpet_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: carpet_vertex_buf.as_entire_binding(),
+            }],
+            label: None,
+        });
+        let carpet_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&bind_group_layout, &carpet_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let carpet_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&carpet_pipeline_layout),
+            module: &shader,
+            entry_point: Some("carpet_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let points_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(size_of::<Config>() as _),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let points_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("points.wgsl"))),
+        });
+        let points_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&points_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let points_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&points_pipeline_layout),
+            module: &points_shader,
             entry_point: None,
             compilation_options: Default::default(),
             cache: None,
@@ -325,9 +569,22 @@ impl SliceRenderer {
             pipeline,
             compute_pipeline,
             color_map_texture,
+            diff_color_map_texture,
+            field_buf,
+            snapshot_buf,
+            snapshot_pending: false,
+            resolution: TEXTURE_DIMS,
             trans_pos_buf: None,
             trans_state_buf: None,
             config_buf: None,
+            points_bind_group_layout,
+            points_pipeline,
+            carpet: false,
+            carpet_vertex_buf,
+            carpet_index_buf,
+            carpet_index_count: carpet_index_data.len(),
+            carpet_bind_group,
+            carpet_pipeline,
         }
     }
 
@@ -357,6 +614,9 @@ impl SliceRenderer {
         let color_map_texture_view = self
             .color_map_texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        let diff_color_map_texture_view = self
+            .diff_color_map_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
 
         self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &self.bind_group_layout,
@@ -393,6 +653,18 @@ impl SliceRenderer {
                     binding: 7,
                     resource: wgpu::BindingResource::TextureView(&color_map_texture_view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: self.field_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: self.snapshot_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: wgpu::BindingResource::TextureView(&diff_color_map_texture_view),
+                },
             ],
             label: None,
         }))
@@ -422,7 +694,22 @@ impl SliceRenderer {
             num_trans: emulator.transducers().len() as u32,
             max_pressure: state.slice.pressure_max,
             scale: 1. / mm,
+            field: match state.slice.field {
+                SliceField::Pressure => 0,
+                SliceField::Intensity => 1,
+            },
+            max_intensity: state.slice.intensity_max,
+            impedance: state.density * state.sound_speed / METER,
+            mask_threshold: if state.slice.mask {
+                state.slice.mask_threshold
+            } else {
+                0.
+            },
+            carpet_height: state.slice.carpet_height * mm,
+            diff: (state.snapshot.taken && state.snapshot.show_diff) as u32,
+            _pad: [0.; 2],
         };
+        self.carpet = state.slice.carpet;
         queue.write_buffer(
             self.config_buf.as_ref().unwrap(),
             0,
@@ -440,7 +727,10 @@ impl SliceRenderer {
             1. / mm,
         ));
         queue.write_buffer(&self.model_buf, 0, bytemuck::cast_slice(model.as_ref()));
-        let slice_size = Vector2::new(state.slice.size.x, state.slice.size.y) / mm;
+        // number of pixels along each side, limited by the texture size
+        let slice_size = (state.slice.size / state.slice.pixel_size)
+            .min(Vector2::new(TEXTURE_DIMS.0 as f32, TEXTURE_DIMS.1 as f32));
+        self.resolution = (slice_size.x.ceil() as u32, slice_size.y.ceil() as u32);
         queue.write_buffer(
             &self.slice_size_buf,
             0,
@@ -448,35 +738,51 @@ impl SliceRenderer {
         );
     }
 
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
     pub fn update_color_map(&mut self, state: &State, queue: &Queue) {
-        let iter = (0..COLOR_MAP_TEXTURE_SIZE).map(|x| x as f64 / COLOR_MAP_TEXTURE_SIZE as f64);
-        let texels = state
-            .slice
-            .color_map
-            .color_map(iter)
-            .into_iter()
-            .flat_map(|color| {
-                [
-                    (color.r * 255.) as u8,
-                    (color.g * 255.) as u8,
-                    (color.b * 255.) as u8,
-                    255,
-                ]
-            })
-            .collect::<Vec<_>>();
-        queue.write_texture(
-            self.color_map_texture.as_image_copy(),
-            bytemuck::cast_slice(&texels),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: None,
-                rows_per_image: None,
-            },
-            wgpu::Extent3d {
-                width: COLOR_MAP_TEXTURE_SIZE,
-                height: 1,
-                depth_or_array_layers: 1,
-            },
+        let write = |texture: &wgpu::Texture, texels: Vec<u8>| {
+            queue.write_texture(
+                texture.as_image_copy(),
+                bytemuck::cast_slice(&texels),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: COLOR_MAP_TEXTURE_SIZE,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        };
+        write(
+            &self.color_map_texture,
+            color_map_texels(state.slice.color_map),
+        );
+        write(
+            &self.diff_color_map_texture,
+            color_map_texels(ColorMap::Bluered),
+        );
+    }
+
+    pub fn take_snapshot(&mut self) {
+        self.snapshot_pending = true;
+    }
+
+    pub fn copy_snapshot(&mut self, encoder: &mut CommandEncoder) {
+        if !std::mem::take(&mut self.snapshot_pending) {
+            return;
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.field_buf,
+            0,
+            &self.snapshot_buf,
+            0,
+            self.field_buf.size(),
         );
     }
 
@@ -496,17 +802,238 @@ impl SliceRenderer {
         pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
         pass.set_pipeline(&self.compute_pipeline);
         pass.dispatch_workgroups(
-            (TEXTURE_DIMS.0 - 1) / WORKGROUP_SIZE.0 + 1,
-            (TEXTURE_DIMS.1 - 1) / WORKGROUP_SIZE.1 + 1,
+            (self.resolution.0.max(1) - 1) / WORKGROUP_SIZE.0 + 1,
+            (self.resolution.1.max(1) - 1) / WORKGROUP_SIZE.1 + 1,
             1,
         );
+        if self.carpet {
+            pass.set_bind_group(1, &self.carpet_bind_group, &[]);
+            pass.set_pipeline(&self.carpet_pipeline);
+            pass.dispatch_workgroups(
+                (CARPET_SIZE - 1) / WORKGROUP_SIZE.0 + 1,
+                (CARPET_SIZE - 1) / WORKGROUP_SIZE.1 + 1,
+                1,
+            );
+        }
+    }
+
+    pub fn compute_at(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        points: &[Vector3],
+    ) -> Result<Vec<f32>, wgpu::BufferAsyncError> {
+        let (Some(trans_pos_buf), Some(trans_state_buf), Some(config_buf)) = (
+            self.trans_pos_buf.as_ref(),
+            self.trans_state_buf.as_ref(),
+            self.config_buf.as_ref(),
+        ) else {
+            return Ok(vec![0.; points.len()]);
+        };
+        if points.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let points = points.iter().map(|p| p.extend(1.)).collect::<Vec<_>>();
+        let points_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Points Buffer"),
+            usage: wgpu::BufferUsages::STORAGE,
+            contents: bytemuck::cast_slice(&points),
+        });
+        let result_size = (points.len() * size_of::<f32>()) as wgpu::BufferAddress;
+        let result_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Points Result Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            size: result_size,
+            mapped_at_creation: false,
+        });
+        let staging_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Points Staging Buffer"),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            size: result_size,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.points_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: trans_pos_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: trans_state_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: config_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: points_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: result_buf.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.set_pipeline(&self.points_pipeline);
+            pass.dispatch_workgroups((points.len() as u32 - 1) / POINTS_WORKGROUP_SIZE + 1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&result_buf, 0, &staging_buf, 0, result_size);
+        queue.submit(Some(encoder.finish()));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let slice = staging_buf.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |r| {
+            let _ = tx.send(r);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        rx.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+        let result = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging_buf.unmap();
+        Ok(result)
     }
 
     pub fn render(&mut self, pass: &mut RenderPass) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
-        pass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
-        pass.set_vertex_buffer(0, self.vertex_buf.slice(..));
-        pass.draw_indexed(0..self.index_count as u32, 0, 0..1);
+        if self.carpet {
+            pass.set_index_buffer(self.carpet_index_buf.slice(..), wgpu::IndexFormat::Uint32);
+            pass.set_vertex_buffer(0, self.carpet_vertex_buf.slice(..));
+            pass.draw_indexed(0..self.carpet_index_count as u32, 0, 0..1);
+        } else {
+            pass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
+            pass.set_vertex_buffer(0, self.vertex_buf.slice(..));
+            pass.draw_indexed(0..self.index_count as u32, 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::{field::pressure_at, geometry_file::grid_geometry};
+
+    fn device() -> Option<(Device, Queue)> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                    backends: wgpu::Backends::PRIMARY,
+                    ..Default::default()
+                });
+                let adapter = instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::default(),
+                        force_fallback_adapter: false,
+                        compatible_surface: None,
+                    })
+                    .await?;
+                adapter
+                    .request_device(
+                        &wgpu::DeviceDescriptor {
+                            label: None,
+                            required_features:
+                                wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                            required_limits: Default::default(),
+                            memory_hints: Default::default(),
+                        },
+                        None,
+                    )
+                    .await
+                    .ok()
+            })
+    }
+
+    #[test]
+    fn compute_at_single_focus() {
+        let Some((device, queue)) = device() else {
+            eprintln!("No GPU adapter is available, skipping");
+            return;
+        };
+
+        let state = State::default();
+        let mut emulator = EmulatorWrapper::new(Default::default(), Default::default());
+        emulator.initialize(None, &grid_geometry(1, 1), &[]);
+        let positions = emulator
+            .transducers()
+            .positions()
+            .iter()
+            .map(|p| p.truncate())
+            .collect::<Vec<_>>();
+        let center = positions.iter().sum::<Vector3>() / positions.len() as f32;
+        let focus = center + Vector3::new(0., 0., 150. * mm);
+        // cancel the propagation phase, so that all the waves arrive at the focus in phase
+        let wavenumber = 2. * PI * 40e3 / state.sound_speed;
+        emulator.iter_mut().for_each(|dev| {
+            dev.transducers
+                .iter_mut()
+                .zip(positions.iter())
+                .for_each(|(tr, p)| {
+                    tr.amp = 1.;
+                    tr.phase = (-wavenumber * p.distance(focus)).rem_euclid(2. * PI);
+                })
+        });
+
+        // the surface format only matters for the render pipeline, which is never used here
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 0,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
+        };
+        let mut slice_renderer = SliceRenderer::new(&device, &surface_config, 1);
+        slice_renderer.initialize(&device, &emulator);
+        slice_renderer.update_trans_pos(&emulator, &queue);
+        slice_renderer.update_trans_state(&emulator, &queue);
+        slice_renderer.update_config(&state, &emulator, &queue);
+
+        let points = [
+            focus,
+            focus + Vector3::new(10. * mm, 0., 0.),
+            focus + Vector3::new(0., 0., 50. * mm),
+        ];
+        let expected = pressure_at(
+            &positions,
+            emulator.transducers().states(),
+            state.sound_speed,
+            &points,
+        );
+        let actual = slice_renderer.compute_at(&device, &queue, &points).unwrap();
+
+        let max = expected[0];
+        assert!(expected[1..].iter().all(|&p| p < max));
+        expected
+            .iter()
+            .zip(actual.iter())
+            .for_each(|(expected, actual)| {
+                assert!(
+                    (expected - actual).abs() <= max * 1e-3,
+                    "expected: {}, actual: {}",
+                    expected,
+                    actual
+                );
+            });
     }
 }
@@ -0,0 +1,60 @@
+@group(0)
+@binding(0)
+var<storage, read> v_tr_pos: array<vec3<f32>>;
+
+@group(0)
+@binding(1)
+var<storage, read> v_tr_state: array<vec4<f32>>;
+
+struct Config {
+    sound_speed: f32,
+    num_trans: u32,
+    max_pressure: f32,
+    scale: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> config: Config;
+
+@group(0)
+@binding(3)
+var<storage, read> points: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> result: array<f32>;
+
+const ULTRASOUND_FREQ: f32 = 40000;
+
+const PI: f32 = radians(180.0);
+const T4010A1_AMPLITUDE: f32 = 55114.85; // [Pa*mm]
+const P0: f32 = T4010A1_AMPLITUDE / (4. * PI);
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= arrayLength(&points) {
+        return;
+    }
+
+    let point = points[id.x].xyz * config.scale;
+
+    let wavenum = 2 * PI * ULTRASOUND_FREQ / (config.sound_speed * config.scale);
+
+    var re: f32 = 0.;
+    var im: f32 = 0.;
+    for (var i: u32 = 0; i < config.num_trans; i++) {
+        let r = distance(v_tr_pos[i] * config.scale, point);
+
+        let amp = v_tr_state[i].x;
+        let phase = v_tr_state[i].y;
+        let en = v_tr_state[i].z;
+
+        let p = -phase - wavenum * r;
+        let a = en * P0 * amp / r;
+        re += a * cos(p);
+        im += a * sin(p);
+    }
+    result[id.x] = sqrt(re * re + im * im);
+}
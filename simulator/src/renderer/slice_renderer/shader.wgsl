@@ -32,6 +32,12 @@ struct Config {
     num_trans: u32,
     max_pressure: f32,
     scale: f32,
+    field: u32,
+    max_intensity: f32,
+    impedance: f32,
+    mask_threshold: f32,
+    carpet_height: f32,
+    diff: u32,
 }
 
 @group(0)
@@ -42,6 +48,22 @@ var<uniform> config: Config;
 @binding(7)
 var color_map: texture_1d<f32>;
 
+@group(0)
+@binding(8)
+var<storage, read_write> field: array<f32>;
+
+@group(0)
+@binding(9)
+var<storage, read> snapshot: array<f32>;
+
+@group(0)
+@binding(10)
+var diff_color_map: texture_1d<f32>;
+
+@group(1)
+@binding(0)
+var<storage, read_write> carpet: array<vec4<f32>>;
+
 @vertex
 fn vs_main(
     @location(0) position: vec4<f32>,
@@ -58,33 +80,32 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     var coord = vertex.tex_coord;
     coord.x *= slice_size.x;
     coord.y *= slice_size.y;
-    return textureLoad(texture, vec2<i32>(coord));
+    let color = textureLoad(texture, vec2<i32>(coord));
+    if color.a == 0.0 {
+        discard;
+    }
+    return color;
 }
 
 const ULTRASOUND_FREQ: f32 = 40000;
 const COLOR_MAP_TEXTURE_SIZE: f32 = 256;
+const TEXTURE_WIDTH: u32 = 1024;
 
 const PI: f32 = radians(180.0);
 const T4010A1_AMPLITUDE: f32 = 55114.85; // [Pa*mm]
 const P0: f32 = T4010A1_AMPLITUDE / (4. * PI);
 
+const FIELD_INTENSITY: u32 = 1;
+
 fn coloring(t: f32) -> vec4<f32> {
     return textureLoad(color_map, u32(clamp(t, 0.0, 1.0) * COLOR_MAP_TEXTURE_SIZE), 0);
 }
 
-@compute
-@workgroup_size(8, 8, 1)
-fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    let id_x = i32(id.x);
-    let id_y = i32(id.y);
-    let width = i32(slice_size.x);
-    let height = i32(slice_size.y);
-    if id_x >= width || id_y >= height {
-        return;
-    }
+fn diff_coloring(t: f32) -> vec4<f32> {
+    return textureLoad(diff_color_map, u32(clamp(t, 0.0, 1.0) * COLOR_MAP_TEXTURE_SIZE), 0);
+}
 
-    let x = f32(id.x) / slice_size.x - 0.5;
-    let y = f32(id.y) / slice_size.y - 0.5;
+fn pressure(x: f32, y: f32) -> f32 {
     let s = mat4x4<f32>(config.scale, 0.0, 0.0, 0.0,
         0.0, config.scale, 0.0, 0.0,
         0.0, 0.0, config.scale, 0.0,
@@ -107,6 +128,70 @@ fn main(@builtin(global_invocation_id) id: vec3<u32>) {
         re += a * cos(p);
         im += a * sin(p);
     }
-    let c = sqrt(re * re + im * im) / config.max_pressure;
-    textureStore(texture, vec2(id_x, id_y), coloring(c));
+    return sqrt(re * re + im * im);
+}
+
+// Value of the selected field for the pressure amplitude `p`
+fn field_value(p: f32) -> f32 {
+    if config.field == FIELD_INTENSITY {
+        return p * p / (2. * config.impedance);
+    }
+    return p;
+}
+
+// Value of the selected field for the pressure amplitude `p`, normalized by its maximum
+fn normalized(p: f32) -> f32 {
+    if config.field == FIELD_INTENSITY {
+        return field_value(p) / config.max_intensity;
+    }
+    return field_value(p) / config.max_pressure;
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let id_x = i32(id.x);
+    let id_y = i32(id.y);
+    let width = i32(slice_size.x);
+    let height = i32(slice_size.y);
+    if id_x >= width || id_y >= height {
+        return;
+    }
+
+    let x = f32(id.x) / slice_size.x - 0.5;
+    let y = f32(id.y) / slice_size.y - 0.5;
+    let p = pressure(x, y);
+    let idx = id.y * TEXTURE_WIDTH + id.x;
+    field[idx] = p;
+
+    var color: vec4<f32>;
+    if config.diff != 0 {
+        color = diff_coloring(0.5 + 0.5 * (normalized(p) - normalized(snapshot[idx])));
+    } else {
+        color = coloring(normalized(p));
+        if field_value(p) < config.mask_threshold {
+            color.a = 0.0;
+        }
+    }
+    textureStore(texture, vec2(id_x, id_y), color);
+}
+
+const CARPET_SIZE: u32 = 256;
+
+// Each carpet vertex is stored as (position, tex_coord) to match the slice vertex layout.
+@compute
+@workgroup_size(8, 8, 1)
+fn carpet_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= CARPET_SIZE || id.y >= CARPET_SIZE {
+        return;
+    }
+
+    let u = f32(id.x) / f32(CARPET_SIZE - 1);
+    let v = f32(id.y) / f32(CARPET_SIZE - 1);
+    let p = pressure(u - 0.5, v - 0.5);
+    let h = config.carpet_height * clamp(normalized(p), 0.0, 1.0);
+
+    let i = 2 * (id.y * CARPET_SIZE + id.x);
+    carpet[i] = vec4(u - 0.5, v - 0.5, h, 1.0);
+    carpet[i + 1] = vec4(u, v, 0.0, 0.0);
 }
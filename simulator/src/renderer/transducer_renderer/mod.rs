@@ -9,11 +9,18 @@ use crate::{
     common::color::{Color, Hsv},
     emulator::EmulatorWrapper,
     error::SimulatorError,
-    Matrix4, Vector3, Vector4,
+    state::TransducerColoring,
+    Matrix4, State, Vector3, Vector4,
 };
 
 use super::DepthTexture;
 
+struct Eye {
+    proj_view_buf: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
 pub struct TransducerRenderer {
     vertex_buf: wgpu::Buffer,
     index_buf: wgpu::Buffer,
@@ -24,6 +31,7 @@ pub struct TransducerRenderer {
     instance_count: u32,
     bind_group: wgpu::BindGroup,
     pipeline: wgpu::RenderPipeline,
+    eyes: [Eye; 2],
 }
 
 #[repr(C)]
@@ -75,6 +83,7 @@ impl TransducerRenderer {
         device: &Device,
         queue: &Queue,
         surface_config: &SurfaceConfiguration,
+        sample_count: u32,
     ) -> Result<Self, SimulatorError> {
         let vertex_size = mem::size_of::<Vertex>();
         let (vertex_data, index_data) = create_vertices();
@@ -149,27 +158,31 @@ impl TransducerRenderer {
             texture_extent,
         );
 
-        let proj_view_buf = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Projection View Buffer"),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            size: size_of::<Matrix4>() as wgpu::BufferAddress,
-            mapped_at_creation: false,
-        });
+        let create_bind_group = |label: &str| {
+            let proj_view_buf = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                size: size_of::<Matrix4>() as wgpu::BufferAddress,
+                mapped_at_creation: false,
+            });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: proj_view_buf.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-            ],
-            label: None,
-        });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: proj_view_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                    },
+                ],
+                label: None,
+            });
+            (proj_view_buf, bind_group)
+        };
+        let (proj_view_buf, bind_group) = create_bind_group("Projection View Buffer");
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
@@ -230,46 +243,69 @@ impl TransducerRenderer {
             },
         ];
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: None,
-                compilation_options: Default::default(),
-                buffers: &vertex_buffers,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: None,
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.view_formats[0],
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent::OVER,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                cull_mode: None,
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DepthTexture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
+        let create_pipeline = |write_mask: wgpu::ColorWrites| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    compilation_options: Default::default(),
+                    buffers: &vertex_buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_config.view_formats[0],
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::SrcAlpha,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent::OVER,
+                        }),
+                        write_mask,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+        let pipeline = create_pipeline(wgpu::ColorWrites::ALL);
+
+        // red/cyan anaglyph
+        let eyes = [
+            ("Left Eye Projection View Buffer", wgpu::ColorWrites::RED),
+            (
+                "Right Eye Projection View Buffer",
+                wgpu::ColorWrites::GREEN | wgpu::ColorWrites::BLUE,
+            ),
+        ]
+        .map(|(label, write_mask)| {
+            let (proj_view_buf, bind_group) = create_bind_group(label);
+            Eye {
+                proj_view_buf,
+                bind_group,
+                pipeline: create_pipeline(write_mask),
+            }
         });
 
         Ok(Self {
@@ -282,6 +318,7 @@ impl TransducerRenderer {
             bind_group,
             proj_view_buf,
             pipeline,
+            eyes,
         })
     }
 
@@ -293,6 +330,19 @@ impl TransducerRenderer {
         );
     }
 
+    pub fn update_eye_cameras(&mut self, proj_views: [Matrix4; 2], queue: &Queue) {
+        self.eyes
+            .iter()
+            .zip(proj_views)
+            .for_each(|(eye, proj_view)| {
+                queue.write_buffer(
+                    &eye.proj_view_buf,
+                    0,
+                    bytemuck::cast_slice(proj_view.as_ref()),
+                );
+            });
+    }
+
     pub fn resize(&mut self, proj_view: Matrix4, queue: &Queue) {
         self.update_camera(proj_view, queue);
     }
@@ -300,6 +350,16 @@ impl TransducerRenderer {
     pub fn render(&mut self, pass: &mut RenderPass) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
+        self.draw(pass);
+    }
+
+    pub fn render_eye(&mut self, pass: &mut RenderPass, eye: usize) {
+        pass.set_pipeline(&self.eyes[eye].pipeline);
+        pass.set_bind_group(0, &self.eyes[eye].bind_group, &[]);
+        self.draw(pass);
+    }
+
+    fn draw(&self, pass: &mut RenderPass) {
         pass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
         pass.set_vertex_buffer(0, self.vertex_buf.slice(..));
         pass.set_vertex_buffer(1, self.model_instance_buf.as_ref().unwrap().slice(..));
@@ -346,13 +406,33 @@ impl TransducerRenderer {
         );
     }
 
-    pub fn update_color(&mut self, emulator: &EmulatorWrapper, queue: &Queue) {
-        let instance_data = emulator
-            .transducers()
-            .states()
+    pub fn update_color(&mut self, emulator: &EmulatorWrapper, state: &State, queue: &Queue) {
+        let states = emulator.transducers().states();
+        let mut instance_data = states
             .iter()
-            .map(|d| coloring_hsv(d.phase / (2.0 * PI), d.amp, d.alpha))
+            .map(|d| match state.coloring {
+                TransducerColoring::PhaseHsv => coloring_hsv(d.phase / (2.0 * PI), d.amp, d.alpha),
+                TransducerColoring::AmplitudeGray => [d.amp, d.amp, d.amp, d.alpha],
+                TransducerColoring::EnableMask => {
+                    let v = if d.enable > 0. { 1. } else { 0.2 };
+                    [v, v, v, d.alpha]
+                }
+            })
             .collect::<Vec<_>>();
+        if state.tint {
+            emulator
+                .transducers()
+                .device_ranges()
+                .enumerate()
+                .for_each(|(dev, range)| {
+                    let tint = state.tint(dev);
+                    instance_data[range].iter_mut().for_each(|c| {
+                        c[0] *= tint.r() as f32 / 255.;
+                        c[1] *= tint.g() as f32 / 255.;
+                        c[2] *= tint.b() as f32 / 255.;
+                    });
+                });
+        }
         queue.write_buffer(
             self.color_instance_buf.as_ref().unwrap(),
             0,
@@ -4,15 +4,48 @@ use autd3_protobuf::{
     simulator_server, CloseRequest, CloseResponse, FromMessage, Geometry, GeometryResponse,
     ReadRequest, RxMessage, SendResponse, TxRawData,
 };
-use parking_lot::RwLock;
 use tonic::{Request, Response, Status};
-use winit::event_loop::EventLoopProxy;
 
-use crate::event::{Signal, UserEvent};
+use crate::{
+    emulator::RxBuffer,
+    event::{EventProxy, SessionId, Signal, UserEvent},
+};
+
+use super::{
+    queue::SignalQueue,
+    record::{RecordKind, Recorder},
+};
 
 pub struct SimulatorServer {
-    pub rx_buf: Arc<RwLock<Vec<autd3_driver::firmware::cpu::RxMessage>>>,
-    pub proxy: EventLoopProxy<UserEvent>,
+    pub multi_client: bool,
+    pub rx_buf: RxBuffer,
+    pub queue: Arc<SignalQueue>,
+    pub proxy: EventProxy,
+    pub recorder: Option<Arc<Recorder>>,
+}
+
+impl SimulatorServer {
+    fn session<T>(&self, req: &Request<T>) -> SessionId {
+        if self.multi_client {
+            req.remote_addr()
+        } else {
+            None
+        }
+    }
+
+    fn record(&self, kind: RecordKind, msg: &impl prost::Message) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(kind, msg);
+        }
+    }
+
+    fn wake(&self, wake: Option<bool>) -> Result<(), Status> {
+        match wake {
+            Some(false) => Ok(()),
+            Some(true) if self.proxy.send_event(UserEvent::SignalQueued).is_ok() => Ok(()),
+            _ => Err(Status::unavailable("Simulator is closed")),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -21,14 +54,10 @@ impl simulator_server::Simulator for SimulatorServer {
         &self,
         req: Request<Geometry>,
     ) -> Result<Response<GeometryResponse>, Status> {
+        self.record(RecordKind::ConfigGeometry, req.get_ref());
+        let session = self.session(&req);
         let geometry = autd3_driver::geometry::Geometry::from_msg(&req.into_inner())?;
-        if self
-            .proxy
-            .send_event(UserEvent::Server(Signal::ConfigGeometry(geometry)))
-            .is_err()
-        {
-            return Err(Status::unavailable("Simulator is closed"));
-        }
+        self.wake(self.queue.push(session, Signal::ConfigGeometry(geometry)))?;
         Ok(Response::new(GeometryResponse {}))
     }
 
@@ -36,44 +65,47 @@ impl simulator_server::Simulator for SimulatorServer {
         &self,
         req: Request<Geometry>,
     ) -> Result<Response<GeometryResponse>, Status> {
+        self.record(RecordKind::UpdateGeometry, req.get_ref());
+        let session = self.session(&req);
         let geometry = autd3_driver::geometry::Geometry::from_msg(&req.into_inner())?;
-        if self
-            .proxy
-            .send_event(UserEvent::Server(Signal::UpdateGeometry(geometry)))
-            .is_err()
-        {
-            return Err(Status::unavailable("Simulator is closed"));
-        }
+        self.wake(self.queue.push(session, Signal::UpdateGeometry(geometry)))?;
         Ok(Response::new(GeometryResponse {}))
     }
 
     async fn send_data(&self, req: Request<TxRawData>) -> Result<Response<SendResponse>, Status> {
+        self.record(RecordKind::Send, req.get_ref());
+        let session = self.session(&req);
         let tx = Vec::<autd3_driver::firmware::cpu::TxMessage>::from_msg(&req.into_inner())?;
-        if self
-            .proxy
-            .send_event(UserEvent::Server(Signal::Send(tx)))
-            .is_err()
-        {
+        let Some((wake, applied)) = self.queue.push_frame(session, tx).await else {
             return Err(Status::unavailable("Simulator is closed"));
+        };
+        self.wake(Some(wake))?;
+        match applied.await {
+            Ok(true) => Ok(Response::new(SendResponse { success: true })),
+            Ok(false) => Err(Status::resource_exhausted(
+                "Frame was dropped because the simulator could not keep up",
+            )),
+            Err(_) => Err(Status::unavailable("Simulator is closed")),
         }
-        Ok(Response::new(SendResponse { success: true }))
     }
 
-    async fn read_data(&self, _: Request<ReadRequest>) -> Result<Response<RxMessage>, Status> {
+    async fn read_data(&self, req: Request<ReadRequest>) -> Result<Response<RxMessage>, Status> {
+        let session = self.session(&req);
         let rx = self.rx_buf.read();
         Ok(Response::new(RxMessage {
-            data: rx.iter().flat_map(|c| [c.data(), c.ack()]).collect(),
+            data: rx
+                .get(&session)
+                .into_iter()
+                .flatten()
+                .flat_map(|c| [c.data(), c.ack()])
+                .collect(),
         }))
     }
 
-    async fn close(&self, _: Request<CloseRequest>) -> Result<Response<CloseResponse>, Status> {
-        if self
-            .proxy
-            .send_event(UserEvent::Server(Signal::Close))
-            .is_err()
-        {
-            return Err(Status::unavailable("Simulator is closed"));
-        }
+    async fn close(&self, req: Request<CloseRequest>) -> Result<Response<CloseResponse>, Status> {
+        self.record(RecordKind::Close, req.get_ref());
+        let session = self.session(&req);
+        self.wake(self.queue.push(session, Signal::Close))?;
         Ok(Response::new(CloseResponse { success: true }))
     }
 }
@@ -0,0 +1,341 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    emulator::DeviceOutputs,
+    error::Result,
+    event::{EventProxy, UserEvent},
+    Vector3,
+};
+
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+const BAD_REQUEST: &str = "400 Bad Request";
+const UNAVAILABLE: &str = "503 Service Unavailable";
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads the request line, the headers and the body of `Content-Length` bytes.
+    /// Returns the status and the message to respond with if the request cannot be read.
+    async fn read(stream: &mut TcpStream) -> std::result::Result<Self, (&'static str, String)> {
+        let malformed = || (BAD_REQUEST, "malformed request".to_string());
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+        let head_len = loop {
+            if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+            if data.len() > MAX_HEAD_SIZE {
+                return Err(malformed());
+            }
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return Err(malformed()),
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+            }
+        };
+
+        let head = std::str::from_utf8(&data[..head_len]).map_err(|_| malformed())?;
+        let mut lines = head.lines();
+        let mut request_line = lines.next().ok_or_else(malformed)?.split_whitespace();
+        let method = request_line.next().ok_or_else(malformed)?.to_string();
+        let path = request_line.next().ok_or_else(malformed)?;
+        let path = path.split('?').next().unwrap_or(path).to_string();
+        let content_length = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .map(|(_, value)| value.trim().parse::<usize>().map_err(|_| malformed()))
+            .transpose()?
+            .unwrap_or(0);
+        if content_length > MAX_BODY_SIZE {
+            return Err((
+                "413 Payload Too Large",
+                format!("body must be at most {} bytes", MAX_BODY_SIZE),
+            ));
+        }
+
+        let mut body = data.split_off(head_len);
+        while body.len() < content_length {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return Err(malformed()),
+                Ok(n) => body.extend_from_slice(&buf[..n]),
+            }
+        }
+        body.truncate(content_length);
+        Ok(Self { method, path, body })
+    }
+}
+
+/// Body of `POST /field`
+#[derive(Deserialize)]
+struct FieldRequest {
+    points: Vec<Vector3>,
+}
+
+fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn error_response(status: &str, message: &str) -> String {
+    response(status, &serde_json::json!({ "error": message }).to_string())
+}
+
+#[derive(Clone)]
+struct Health {
+    start: Instant,
+    num_devices: Arc<AtomicUsize>,
+    connected: Arc<AtomicBool>,
+    outputs: DeviceOutputs,
+    proxy: EventProxy,
+}
+
+impl Health {
+    async fn serve(self, mut stream: TcpStream) {
+        let response = match Request::read(&mut stream).await {
+            Ok(request) => match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/health") => self.health(),
+                ("POST", "/field") => self.field(&request.body).await,
+                _ => error_response("404 Not Found", "not found"),
+            },
+            Err((status, message)) => error_response(status, &message),
+        };
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    fn health(&self) -> String {
+        let body = serde_json::json!({
+            "connected": self.connected.load(Ordering::Relaxed),
+            "num_devices": self.num_devices.load(Ordering::Relaxed),
+            "uptime_s": self.start.elapsed().as_secs(),
+            "devices": *self.outputs.read(),
+        })
+        .to_string();
+        response("200 OK", &body)
+    }
+
+    /// Computes the field in the event loop, which owns the emulators and the renderer
+    async fn field(&self, body: &[u8]) -> String {
+        let request: FieldRequest = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return error_response(BAD_REQUEST, &e.to_string()),
+        };
+        let (sender, receiver) = oneshot::channel();
+        if self
+            .proxy
+            .send_event(UserEvent::FieldQuery {
+                points: request.points,
+                result: sender,
+            })
+            .is_err()
+        {
+            return error_response(UNAVAILABLE, "simulator is not running");
+        }
+        match receiver.await {
+            Ok(Ok(pressure)) => response(
+                "200 OK",
+                &serde_json::json!({ "pressure": pressure }).to_string(),
+            ),
+            Ok(Err(e)) => error_response("500 Internal Server Error", &e),
+            Err(_) => error_response(UNAVAILABLE, "simulator is not running"),
+        }
+    }
+}
+
+pub struct HealthServer {
+    port: u16,
+    server_th: JoinHandle<Result<()>>,
+    shutdown: oneshot::Sender<()>,
+}
+
+impl HealthServer {
+    /// Binds the port before returning, so that a port in use is reported to the caller.
+    ///
+    /// - `GET /health` returns the status as JSON.
+    /// - `POST /field` with `{"points": [[x, y, z], ...]}` returns the pressure amplitude [Pa] at the points as `{"pressure": [...]}`.
+    ///   The points are in the same unit as the positions in the settings file.
+    ///
+    /// Any other request is answered with 404.
+    pub fn new(
+        runtime: &Runtime,
+        port: u16,
+        num_devices: Arc<AtomicUsize>,
+        connected: Arc<AtomicBool>,
+        outputs: DeviceOutputs,
+        proxy: EventProxy,
+    ) -> Result<Self> {
+        let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let listener = {
+            let _guard = runtime.enter();
+            TcpListener::from_std(listener)?
+        };
+        tracing::info!(
+            "Health endpoint is available on http://0.0.0.0:{}/health",
+            port
+        );
+
+        let (sender_shutdown, mut receiver_shutdown) = oneshot::channel::<()>();
+        let health = Health {
+            start: Instant::now(),
+            num_devices,
+            connected,
+            outputs,
+            proxy,
+        };
+
+        let server_th = runtime.spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut receiver_shutdown => break,
+                    accepted = listener.accept() => {
+                        match accepted {
+                            Ok((stream, _)) => {
+                                tokio::spawn(health.clone().serve(stream));
+                            }
+                            Err(e) => tracing::warn!("Failed to accept health request: {}", e),
+                        }
+                    }
+                }
+            }
+            Ok(())
+        });
+
+        Ok(Self {
+            port,
+            server_th,
+            shutdown: sender_shutdown,
+        })
+    }
+
+    /// Port the endpoint is bound to, which is assigned by the OS if 0 is given
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub async fn shutdown(self) -> Result<()> {
+        let Self {
+            server_th,
+            shutdown,
+            ..
+        } = self;
+        let _ = shutdown.send(());
+        server_th.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the event loop is not running, so field queries are answered with 503
+    fn server(runtime: &Runtime, port: u16) -> Result<HealthServer> {
+        HealthServer::new(
+            runtime,
+            port,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            EventProxy::Channel(std::sync::mpsc::channel().0),
+        )
+    }
+
+    fn post(path: &str, body: &str) -> String {
+        format!(
+            "POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        )
+    }
+
+    async fn status(port: u16, request: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn routes() {
+        let runtime = Runtime::new().unwrap();
+        let server = server(&runtime, 0).unwrap();
+        let port = server.port();
+        runtime.block_on(async {
+            for (request, expected) in [
+                (
+                    "GET /health HTTP/1.1\r\n\r\n".to_string(),
+                    "HTTP/1.1 200 OK",
+                ),
+                (
+                    "GET /health?verbose HTTP/1.1\r\n\r\n".to_string(),
+                    "HTTP/1.1 200 OK",
+                ),
+                (
+                    "GET / HTTP/1.1\r\n\r\n".to_string(),
+                    "HTTP/1.1 404 Not Found",
+                ),
+                (post("/health", ""), "HTTP/1.1 404 Not Found"),
+                (
+                    "GET /field HTTP/1.1\r\n\r\n".to_string(),
+                    "HTTP/1.1 404 Not Found",
+                ),
+                (
+                    post("/field", r#"{"points": [[0, 0, 150]]}"#),
+                    "HTTP/1.1 503 Service Unavailable",
+                ),
+                (post("/field", "[0, 0, 150]"), "HTTP/1.1 400 Bad Request"),
+                (
+                    "POST /field HTTP/1.1\r\nContent-Length: x\r\n\r\n".to_string(),
+                    "HTTP/1.1 400 Bad Request",
+                ),
+                (
+                    format!(
+                        "POST /field HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                        MAX_BODY_SIZE + 1
+                    ),
+                    "HTTP/1.1 413 Payload Too Large",
+                ),
+                ("GET\r\n\r\n".to_string(), "HTTP/1.1 400 Bad Request"),
+            ] {
+                assert_eq!(expected, status(port, &request).await, "{:?}", request);
+            }
+            server.shutdown().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn port_in_use() {
+        let runtime = Runtime::new().unwrap();
+        let listener = std::net::TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(server(&runtime, port).is_err());
+    }
+}
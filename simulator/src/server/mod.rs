@@ -1,20 +1,46 @@
 mod grpc;
+mod health;
+mod queue;
+mod record;
 
-use crate::error::Result;
-use crate::event::UserEvent;
-use parking_lot::RwLock;
+pub use health::HealthServer;
+pub use queue::{QueuePolicy, SignalQueue};
+pub use record::{Recorder, Replayer};
+
+use crate::emulator::RxBuffer;
+use crate::error::{Result, SimulatorError};
+use crate::event::EventProxy;
 use tokio::runtime::Runtime;
-use winit::event_loop::EventLoopProxy;
 
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use autd3_driver::firmware::cpu::RxMessage;
 use autd3_protobuf::{ecat_light_server::EcatLightServer, lightweight::LightweightServer};
 use futures_util::FutureExt;
 use std::net::ToSocketAddrs;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 
+/// HTTP/2 settings of the gRPC server. `None` leaves tonic's default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrpcOptions {
+    pub keepalive_interval: Option<Duration>,
+    pub keepalive_timeout: Option<Duration>,
+    /// Maximum number of requests processed at once on each connection, not the number of connections
+    pub concurrency_limit_per_connection: Option<usize>,
+}
+
+impl GrpcOptions {
+    fn apply(&self, builder: tonic::transport::Server) -> tonic::transport::Server {
+        let builder = builder
+            .http2_keepalive_interval(self.keepalive_interval)
+            .http2_keepalive_timeout(self.keepalive_timeout);
+        match self.concurrency_limit_per_connection {
+            Some(limit) => builder.concurrency_limit_per_connection(limit),
+            None => builder,
+        }
+    }
+}
+
 #[allow(clippy::type_complexity)]
 pub struct Server {
     server_th: JoinHandle<Result<()>>,
@@ -22,23 +48,53 @@ pub struct Server {
 }
 
 impl Server {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         runtime: &Runtime,
         port: u16,
+        uds: Option<PathBuf>,
         lightweight: bool,
-        rx_buf: Arc<RwLock<Vec<RxMessage>>>,
-        proxy: EventLoopProxy<UserEvent>,
+        grpc_options: GrpcOptions,
+        multi_client: bool,
+        rx_buf: RxBuffer,
+        queue: Arc<SignalQueue>,
+        proxy: EventProxy,
+        recorder: Option<Arc<Recorder>>,
     ) -> Result<Self> {
+        if uds.is_some() {
+            if cfg!(not(unix)) {
+                return Err(SimulatorError::UdsNotSupported);
+            }
+            if lightweight {
+                return Err(SimulatorError::UdsWithLightweight);
+            }
+        }
+        #[cfg(unix)]
+        if let Some(path) = &uds {
+            use std::os::unix::fs::FileTypeExt;
+            match std::fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+                Ok(_) => return Err(SimulatorError::UdsPathNotSocket(path.clone())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
         let (sender_shutdown, receiver_shutdown) = oneshot::channel::<()>();
 
         let server_th = runtime.spawn({
             async move {
-                let builder = tonic::transport::Server::builder().add_service(
-                    autd3_protobuf::simulator_server::SimulatorServer::new(grpc::SimulatorServer {
-                        rx_buf,
-                        proxy,
-                    }),
-                );
+                let builder = grpc_options
+                    .apply(tonic::transport::Server::builder())
+                    .add_service(autd3_protobuf::simulator_server::SimulatorServer::new(
+                        grpc::SimulatorServer {
+                            multi_client,
+                            rx_buf,
+                            queue,
+                            proxy,
+                            recorder,
+                        },
+                    ));
                 let builder = if lightweight {
                     builder.add_service(EcatLightServer::new(LightweightServer::new(move || {
                         autd3_link_simulator::Simulator::builder(
@@ -48,16 +104,30 @@ impl Server {
                 } else {
                     builder
                 };
-                builder
-                    .serve_with_shutdown(
-                        format!("0.0.0.0:{port}")
-                            .to_socket_addrs()
-                            .unwrap()
-                            .next()
-                            .unwrap(),
-                        receiver_shutdown.map(drop),
-                    )
-                    .await?;
+                match uds {
+                    #[cfg(unix)]
+                    Some(path) => {
+                        let listener = tokio::net::UnixListener::bind(&path)?;
+                        builder
+                            .serve_with_incoming_shutdown(
+                                tokio_stream::wrappers::UnixListenerStream::new(listener),
+                                receiver_shutdown.map(drop),
+                            )
+                            .await?;
+                    }
+                    _ => {
+                        builder
+                            .serve_with_shutdown(
+                                format!("0.0.0.0:{port}")
+                                    .to_socket_addrs()
+                                    .unwrap()
+                                    .next()
+                                    .unwrap(),
+                                receiver_shutdown.map(drop),
+                            )
+                            .await?;
+                    }
+                }
                 Ok(())
             }
         });
@@ -0,0 +1,254 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use autd3_driver::firmware::cpu::TxMessage;
+use parking_lot::Mutex;
+use tokio::sync::{oneshot, Notify};
+
+use crate::event::{SessionId, Signal};
+
+const DROP_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What to do with a new frame when the queue is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Discard the oldest queued frame, and report it as dropped to its request
+    #[default]
+    DropOldest,
+    /// Hold the request until the simulator has applied the queued frames
+    Block,
+}
+
+/// Whether a queued frame was applied, resolved when it leaves the queue
+pub type FrameResult = oneshot::Receiver<bool>;
+
+struct Entry {
+    session: SessionId,
+    signal: Signal,
+    applied: Option<oneshot::Sender<bool>>,
+}
+
+impl Entry {
+    fn is_frame(&self) -> bool {
+        matches!(self.signal, Signal::Send(_))
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    queue: VecDeque<Entry>,
+    frames: usize,
+    closed: bool,
+}
+
+/// Bounded queue of the requests from the gRPC server to the event loop
+///
+/// Geometry and close requests go through the same queue as the frames, so that the simulator applies them in the order they were received.
+/// Only frames count towards the depth and can be dropped.
+pub struct SignalQueue {
+    depth: usize,
+    policy: QueuePolicy,
+    entries: Mutex<Entries>,
+    not_full: Notify,
+    dropped: AtomicU64,
+    last_report: Mutex<Instant>,
+}
+
+impl SignalQueue {
+    pub fn new(depth: usize, policy: QueuePolicy) -> Self {
+        Self {
+            depth: depth.max(1),
+            policy,
+            entries: Mutex::new(Entries::default()),
+            not_full: Notify::new(),
+            dropped: AtomicU64::new(0),
+            last_report: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Queues a geometry or close request, which is never dropped.
+    /// Returns `Some(true)` if the queue was empty, i.e., the event loop has to be woken up, and `None` if the queue is closed.
+    pub fn push(&self, session: SessionId, signal: Signal) -> Option<bool> {
+        let mut entries = self.entries.lock();
+        if entries.closed {
+            return None;
+        }
+        let was_empty = entries.queue.is_empty();
+        entries.queue.push_back(Entry {
+            session,
+            signal,
+            applied: None,
+        });
+        Some(was_empty)
+    }
+
+    /// Queues a frame, dropping the oldest frame or waiting for room if the queue is full.
+    /// Returns whether the event loop has to be woken up, and the result of the frame, or `None` if the queue is closed.
+    pub async fn push_frame(
+        &self,
+        session: SessionId,
+        tx: Vec<TxMessage>,
+    ) -> Option<(bool, FrameResult)> {
+        loop {
+            let notified = self.not_full.notified();
+            {
+                let mut entries = self.entries.lock();
+                if entries.closed {
+                    return None;
+                }
+                if entries.frames < self.depth || self.policy == QueuePolicy::DropOldest {
+                    if entries.frames >= self.depth {
+                        if let Some(i) = entries.queue.iter().position(Entry::is_frame) {
+                            if let Some(applied) =
+                                entries.queue.remove(i).and_then(|entry| entry.applied)
+                            {
+                                let _ = applied.send(false);
+                            }
+                            entries.frames -= 1;
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    let was_empty = entries.queue.is_empty();
+                    let (sender, receiver) = oneshot::channel();
+                    entries.queue.push_back(Entry {
+                        session,
+                        signal: Signal::Send(tx),
+                        applied: Some(sender),
+                    });
+                    entries.frames += 1;
+                    return Some((was_empty, receiver));
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Takes all queued requests and passes them to `f` in the order they were received
+    pub fn drain(&self, mut f: impl FnMut(SessionId, &Signal)) {
+        let queue = {
+            let mut entries = self.entries.lock();
+            entries.frames = 0;
+            std::mem::take(&mut entries.queue)
+        };
+        self.not_full.notify_waiters();
+        queue.into_iter().for_each(|entry| {
+            f(entry.session, &entry.signal);
+            if let Some(applied) = entry.applied {
+                let _ = applied.send(true);
+            }
+        });
+    }
+
+    /// Discards the queued requests and refuses new ones, so that no request waits for the simulator after it has stopped
+    pub fn close(&self) {
+        {
+            let mut entries = self.entries.lock();
+            entries.closed = true;
+            entries.frames = 0;
+            entries.queue.clear();
+        }
+        self.not_full.notify_waiters();
+    }
+
+    /// Number of frames dropped since the last report, at most once per report interval
+    pub fn dropped_report(&self) -> Option<u64> {
+        let mut last_report = self.last_report.lock();
+        if last_report.elapsed() < DROP_REPORT_INTERVAL {
+            return None;
+        }
+        *last_report = Instant::now();
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        (dropped > 0).then_some(dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn kinds(queue: &SignalQueue) -> Vec<&'static str> {
+        let mut kinds = Vec::new();
+        queue.drain(|_, signal| {
+            kinds.push(match signal {
+                Signal::ConfigGeometry(_) => "config",
+                Signal::UpdateGeometry(_) => "update",
+                Signal::Send(_) => "send",
+                Signal::Close => "close",
+            })
+        });
+        kinds
+    }
+
+    #[tokio::test]
+    async fn drain_keeps_order() {
+        let queue = SignalQueue::new(8, QueuePolicy::DropOldest);
+        let (wake, first) = queue.push_frame(None, Vec::new()).await.unwrap();
+        assert!(wake);
+        assert_eq!(Some(false), queue.push(None, Signal::Close));
+        let (wake, second) = queue.push_frame(None, Vec::new()).await.unwrap();
+        assert!(!wake);
+
+        assert_eq!(vec!["send", "close", "send"], kinds(&queue));
+        assert_eq!(Ok(true), first.await);
+        assert_eq!(Ok(true), second.await);
+        assert!(kinds(&queue).is_empty());
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_reports_dropped_frame() {
+        let queue = SignalQueue::new(2, QueuePolicy::DropOldest);
+        let (_, first) = queue.push_frame(None, Vec::new()).await.unwrap();
+        queue.push(None, Signal::Close);
+        let (_, second) = queue.push_frame(None, Vec::new()).await.unwrap();
+        let (_, third) = queue.push_frame(None, Vec::new()).await.unwrap();
+
+        assert_eq!(vec!["close", "send", "send"], kinds(&queue));
+        assert_eq!(Ok(false), first.await);
+        assert_eq!(Ok(true), second.await);
+        assert_eq!(Ok(true), third.await);
+        assert_eq!(1, queue.dropped.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_drain() {
+        let queue = Arc::new(SignalQueue::new(1, QueuePolicy::Block));
+        let (_, first) = queue.push_frame(None, Vec::new()).await.unwrap();
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue
+                    .push_frame(None, Vec::new())
+                    .await
+                    .map(|(wake, _)| wake)
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(vec!["send"], kinds(&queue));
+        assert_eq!(Ok(true), first.await);
+        assert_eq!(Some(true), blocked.await.unwrap());
+        assert_eq!(0, queue.dropped.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn close_releases_requests() {
+        let queue = Arc::new(SignalQueue::new(1, QueuePolicy::Block));
+        let (_, first) = queue.push_frame(None, Vec::new()).await.unwrap();
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push_frame(None, Vec::new()).await.is_none() }
+        });
+        tokio::task::yield_now().await;
+
+        queue.close();
+        assert!(first.await.is_err());
+        assert!(blocked.await.unwrap());
+        assert_eq!(None, queue.push(None, Signal::Close));
+    }
+}
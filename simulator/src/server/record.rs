@@ -0,0 +1,187 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use autd3_protobuf::FromMessage;
+use parking_lot::Mutex;
+use prost::Message;
+
+use crate::{
+    error::Result,
+    event::{EventProxy, Signal, UserEvent},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecordKind {
+    ConfigGeometry = 0,
+    UpdateGeometry = 1,
+    Send = 2,
+    Close = 3,
+}
+
+impl RecordKind {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::ConfigGeometry),
+            1 => Some(Self::UpdateGeometry),
+            2 => Some(Self::Send),
+            3 => Some(Self::Close),
+            _ => None,
+        }
+    }
+}
+
+struct Record {
+    kind: RecordKind,
+    elapsed: Duration,
+    data: Vec<u8>,
+}
+
+pub struct Recorder {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            start: Instant::now(),
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn record(&self, kind: RecordKind, msg: &impl Message) {
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        let data = msg.encode_to_vec();
+        let mut writer = self.writer.lock();
+        let r = writer
+            .write_all(&[kind as u8])
+            .and_then(|_| writer.write_all(&elapsed.to_le_bytes()))
+            .and_then(|_| writer.write_all(&(data.len() as u32).to_le_bytes()))
+            .and_then(|_| writer.write_all(&data))
+            .and_then(|_| writer.flush());
+        if let Err(e) = r {
+            tracing::error!("Failed to record {:?}: {}", kind, e);
+        }
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<Record>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    loop {
+        let mut kind = [0u8; 1];
+        if reader.read(&mut kind)? == 0 {
+            break;
+        }
+        let mut elapsed = [0u8; 8];
+        reader.read_exact(&mut elapsed)?;
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let mut data = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut data)?;
+        let Some(kind) = RecordKind::from_u8(kind[0]) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown record kind: {}", kind[0]),
+            )
+            .into());
+        };
+        records.push(Record {
+            kind,
+            elapsed: Duration::from_nanos(u64::from_le_bytes(elapsed)),
+            data,
+        });
+    }
+    Ok(records)
+}
+
+fn to_signal(record: &Record) -> Result<Signal> {
+    let signal = match record.kind {
+        RecordKind::ConfigGeometry => {
+            Signal::ConfigGeometry(autd3_driver::geometry::Geometry::from_msg(
+                &autd3_protobuf::Geometry::decode(record.data.as_slice())?,
+            )?)
+        }
+        RecordKind::UpdateGeometry => {
+            Signal::UpdateGeometry(autd3_driver::geometry::Geometry::from_msg(
+                &autd3_protobuf::Geometry::decode(record.data.as_slice())?,
+            )?)
+        }
+        RecordKind::Send => Signal::Send(Vec::<autd3_driver::firmware::cpu::TxMessage>::from_msg(
+            &autd3_protobuf::TxRawData::decode(record.data.as_slice())?,
+        )?),
+        RecordKind::Close => Signal::Close,
+    };
+    Ok(signal)
+}
+
+pub struct Replayer {
+    stop: Arc<AtomicBool>,
+    th: Option<JoinHandle<()>>,
+}
+
+impl Replayer {
+    pub fn new(path: PathBuf, looping: bool, time_scale: f32, proxy: EventProxy) -> Result<Self> {
+        let records = read_records(&path)?;
+        tracing::info!(
+            "Replaying {} records from {}",
+            records.len(),
+            path.display()
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let th = std::thread::spawn({
+            let stop = stop.clone();
+            move || loop {
+                let start = Instant::now();
+                for record in &records {
+                    let at = record.elapsed.div_f32(time_scale.max(f32::EPSILON));
+                    while start.elapsed() < at {
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        std::thread::sleep(
+                            at.saturating_sub(start.elapsed())
+                                .min(Duration::from_millis(10)),
+                        );
+                    }
+                    let signal = match to_signal(record) {
+                        Ok(signal) => signal,
+                        Err(e) => {
+                            tracing::error!("Failed to decode record: {}", e);
+                            return;
+                        }
+                    };
+                    if proxy.send_event(UserEvent::Server(None, signal)).is_err() {
+                        return;
+                    }
+                }
+                if !looping || stop.load(Ordering::Relaxed) {
+                    tracing::info!("Replay finished");
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { stop, th: Some(th) })
+    }
+}
+
+impl Drop for Replayer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(th) = self.th.take() {
+            let _ = th.join();
+        }
+    }
+}
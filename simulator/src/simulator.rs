@@ -1,50 +1,234 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use autd3_driver::{ethercat::DcSysTime, geometry::Geometry};
+use autd3_server_shutdown::shutdown_signal;
 use parking_lot::RwLock;
-use tokio::runtime::{Builder, Runtime};
+use tokio::{
+    runtime::{Builder, Runtime},
+    sync::oneshot,
+};
 use wgpu::InstanceFlags;
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalPosition,
     event_loop::{ActiveEventLoop, ControlFlow, EventLoopProxy},
     window::Window,
 };
 
 use crate::{
-    emulator::EmulatorWrapper,
+    common::transform,
+    emulator::{DeviceOutputs, EmulatorWrapper},
     error::Result,
-    event::{EventResult, UserEvent},
+    event::{EventProxy, EventResult, SessionId, Signal, UserEvent},
+    field::pressure_at,
     renderer::Renderer,
-    server::Server,
+    server::{GrpcOptions, HealthServer, QueuePolicy, Recorder, Replayer, Server, SignalQueue},
     state::State,
     update_flag::UpdateFlag,
+    watcher::SettingsWatcher,
+    Vector3,
 };
 
+#[derive(Default)]
+pub struct RunOptions {
+    pub preview_geometry: Option<Geometry>,
+    pub settings_path: Option<PathBuf>,
+    pub watch_settings: bool,
+    pub health_port: Option<u16>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_loop: bool,
+    pub grpc: GrpcOptions,
+    pub multi_client: bool,
+    pub queue_depth: usize,
+    pub queue_policy: QueuePolicy,
+}
+
+const UNFOCUSED_FRAME_INTERVAL: Duration = Duration::from_millis(200);
+const HEADLESS_UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct Simulator {
     runtime: Runtime,
     server: Option<Server>,
+    health_server: Option<HealthServer>,
+    num_devices: Arc<AtomicUsize>,
+    connected: Arc<AtomicBool>,
+    clients: Vec<SessionId>,
+    queue: Arc<SignalQueue>,
     emulator: EmulatorWrapper,
     instance: wgpu::Instance,
     repaint_proxy: Option<EventLoopProxy<UserEvent>>,
     windows_next_repaint_time: Option<Instant>,
+    last_paint_time: Instant,
+    focused: bool,
     window: Option<Arc<Window>>,
     renderer: Option<Renderer>,
     run_result: Result<()>,
     update_flag: UpdateFlag,
     state: State,
+    preview_geometry: Option<Geometry>,
+    settings_path: Option<PathBuf>,
+    last_save: Instant,
+    _watcher: Option<SettingsWatcher>,
+    _replayer: Option<Replayer>,
 }
 
 impl Simulator {
-    pub fn run(event_loop: winit::event_loop::EventLoop<UserEvent>, state: State) -> Result<State> {
+    pub fn run(
+        event_loop: winit::event_loop::EventLoop<UserEvent>,
+        state: State,
+        options: RunOptions,
+    ) -> Result<State> {
+        let mut app = Self::new(
+            state,
+            options,
+            EventProxy::EventLoop(event_loop.create_proxy()),
+            Some(event_loop.create_proxy()),
+        )?;
+
+        event_loop.run_app(&mut app)?;
+
+        app.run_result?;
+
+        Ok(app.state)
+    }
+
+    /// Run the server and the emulators without a window until Ctrl+C, or Ctrl-Break on Windows, is pressed.
+    ///
+    /// No window or GPU device is created in this mode, so the slice, screenshots and the control panel are not available.
+    /// Field queries (`POST /field` on the health endpoint) are computed on the CPU instead, and recording and replaying work as usual.
+    pub fn run_headless(state: State, options: RunOptions) -> Result<State> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut app = Self::new(state, options, EventProxy::Channel(sender), None)?;
+
+        let exit = Arc::new(AtomicBool::new(false));
+        app.runtime.spawn({
+            let exit = exit.clone();
+            async move {
+                shutdown_signal().await;
+                exit.store(true, Ordering::Relaxed);
+            }
+        });
+
+        app.config_preview_geometry();
+        while !exit.load(Ordering::Relaxed) && app.headless_step(&receiver) {}
+
+        app.shutdown();
+
+        Ok(app.state)
+    }
+
+    /// Handles an event, or updates the emulators if none arrives within [`HEADLESS_UPDATE_INTERVAL`].
+    /// Returns `false` if the event senders are dropped.
+    fn headless_step(&mut self, receiver: &Receiver<UserEvent>) -> bool {
+        if self.state.auto_play {
+            self.state.real_time =
+                (DcSysTime::now().sys_time() as f64 * self.state.time_scale as f64) as _;
+        }
+        let query = match receiver.recv_timeout(HEADLESS_UPDATE_INTERVAL) {
+            Ok(UserEvent::FieldQuery { points, result }) => {
+                self.update(None);
+                Some((points, result))
+            }
+            Ok(event) => {
+                self.update(Some(&event));
+                None
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.update(None);
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => return false,
+        };
+        if self.update_flag.contains(UpdateFlag::UPDATE_TRANS_STATE) {
+            self.emulator.update_transducers(self.state.mod_enable);
+        }
+        // there is no renderer to consume the flags
+        self.update_flag = UpdateFlag::empty();
+        if let Some((points, result)) = query {
+            self.answer_field_query(&points, result);
+        }
+        if std::mem::take(&mut self.state.save_request) || self.autosave_due() {
+            self.save_settings();
+        }
+        true
+    }
+
+    fn new(
+        state: State,
+        options: RunOptions,
+        proxy: EventProxy,
+        repaint_proxy: Option<EventLoopProxy<UserEvent>>,
+    ) -> Result<Self> {
+        let RunOptions {
+            preview_geometry,
+            settings_path,
+            watch_settings,
+            health_port,
+            record,
+            replay,
+            replay_loop,
+            grpc,
+            multi_client,
+            queue_depth,
+            queue_policy,
+        } = options;
+
+        transform::set_left_handed(state.left_handed);
+
         let runtime = Builder::new_multi_thread().enable_all().build()?;
 
+        let recorder = record.map(Recorder::create).transpose()?.map(Arc::new);
+
         let rx_buf = Arc::new(RwLock::default());
+        let queue = Arc::new(SignalQueue::new(queue_depth, queue_policy));
         let server = Server::new(
             &runtime,
             state.port,
+            state.uds.as_ref().map(PathBuf::from),
             state.lightweight,
+            grpc,
+            multi_client,
             rx_buf.clone(),
-            event_loop.create_proxy(),
+            queue.clone(),
+            proxy.clone(),
+            recorder,
         )?;
+        tracing::info!(
+            "Waiting for client connection on {} (lightweight mode: {})",
+            state.address(),
+            if state.lightweight { "on" } else { "off" }
+        );
+
+        let num_devices = Arc::new(AtomicUsize::new(0));
+        let connected = Arc::new(AtomicBool::new(false));
+        let outputs = DeviceOutputs::default();
+        let health_server = health_port
+            .map(|port| {
+                HealthServer::new(
+                    &runtime,
+                    port,
+                    num_devices.clone(),
+                    connected.clone(),
+                    outputs.clone(),
+                    proxy.clone(),
+                )
+            })
+            .transpose()?;
+
+        let replayer = replay
+            .map(|path| Replayer::new(path, replay_loop, state.time_scale, proxy.clone()))
+            .transpose()?;
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
@@ -56,25 +240,69 @@ impl Simulator {
             ..Default::default()
         });
 
-        let mut app = Self {
+        Ok(Self {
             runtime,
             instance,
-            repaint_proxy: Some(event_loop.create_proxy()),
+            repaint_proxy,
             server: Some(server),
-            emulator: EmulatorWrapper::new(rx_buf),
+            health_server,
+            num_devices,
+            connected,
+            clients: Vec::new(),
+            queue,
+            emulator: EmulatorWrapper::new(rx_buf, outputs),
             windows_next_repaint_time: None,
+            last_paint_time: Instant::now(),
+            focused: true,
             window: None,
             renderer: None,
             run_result: Ok(()),
             update_flag: UpdateFlag::empty(),
             state,
-        };
-
-        event_loop.run_app(&mut app)?;
+            preview_geometry,
+            _watcher: settings_path
+                .clone()
+                .filter(|_| watch_settings)
+                .map(|path| SettingsWatcher::new(path, proxy.clone())),
+            settings_path,
+            last_save: Instant::now(),
+            _replayer: replayer,
+        })
+    }
 
-        app.run_result?;
+    /// Compute the acoustic pressure amplitude at each of `points`
+    ///
+    /// Without a renderer, i.e., in [`Self::run_headless`], the field is computed on the CPU with [`pressure_at`].
+    /// Outside of the crate, this is reached through `POST /field` on the health endpoint.
+    pub fn compute_at(&self, points: &[Vector3]) -> Result<Vec<f32>> {
+        if !self.emulator.initialized() {
+            return Ok(vec![0.; points.len()]);
+        }
+        match &self.renderer {
+            Some(renderer) => renderer.compute_at(points),
+            None => {
+                let transducers = self.emulator.transducers();
+                let positions = transducers
+                    .positions()
+                    .iter()
+                    .map(|p| p.truncate())
+                    .collect::<Vec<_>>();
+                Ok(pressure_at(
+                    &positions,
+                    transducers.states(),
+                    self.state.sound_speed,
+                    points,
+                ))
+            }
+        }
+    }
 
-        Ok(app.state)
+    fn answer_field_query(
+        &self,
+        points: &[Vector3],
+        result: oneshot::Sender<std::result::Result<Vec<f32>, String>>,
+    ) {
+        let _ = result.send(self.compute_at(points).map_err(|e| e.to_string()));
     }
 
     fn initialize(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
@@ -92,11 +320,43 @@ impl Simulator {
         tracing::info!("Initializing window...");
         let viewport_builder = egui::ViewportBuilder::default()
             .with_inner_size([self.state.window_size.0 as _, self.state.window_size.1 as _])
+            .with_maximized(self.state.maximized)
             .with_visible(false);
         let window = egui_winit::create_window(egui_ctx, event_loop, &viewport_builder)?;
+        if let Some(position) = self.state.window_position {
+            Self::restore_position(&window, event_loop, position);
+        }
         Ok(window)
     }
 
+    fn restore_position(window: &Window, event_loop: &ActiveEventLoop, (x, y): (i32, i32)) {
+        let on_monitor = event_loop.available_monitors().any(|monitor| {
+            let pos = monitor.position();
+            let size = monitor.size();
+            (pos.x..pos.x + size.width as i32).contains(&x)
+                && (pos.y..pos.y + size.height as i32).contains(&y)
+        });
+        let position = if on_monitor {
+            PhysicalPosition::new(x, y)
+        } else {
+            match event_loop
+                .primary_monitor()
+                .or_else(|| event_loop.available_monitors().next())
+            {
+                Some(monitor) => {
+                    tracing::warn!(
+                        "Saved window position ({}, {}) is outside of available monitors",
+                        x,
+                        y
+                    );
+                    monitor.position()
+                }
+                None => return,
+            }
+        };
+        window.set_outer_position(position);
+    }
+
     fn init_run_state(&mut self, egui_ctx: egui::Context, window: Window) -> Result<()> {
         let window = Arc::new(window);
 
@@ -111,52 +371,205 @@ impl Simulator {
         ))?);
         self.window = Some(window);
 
+        self.config_preview_geometry();
+
         Ok(())
     }
 
+    fn config_preview_geometry(&mut self) {
+        if let Some(geometry) = self.preview_geometry.take() {
+            tracing::info!(
+                "Previewing geometry with {} devices",
+                geometry.num_devices()
+            );
+            self.config_geometry(None, &geometry);
+        }
+    }
+
+    fn config_geometry(&mut self, session: SessionId, geometry: &Geometry) {
+        // a real client replaces the preview geometry
+        if session.is_some() {
+            self.emulator.remove(None);
+        }
+        self.emulator
+            .initialize(session, geometry, &self.state.devices);
+        self.reinitialize_renderer();
+        self.update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+    }
+
+    /// Track which sessions have configured a geometry and are not closed yet, for the health endpoint
+    fn set_client(&mut self, session: SessionId, connected: bool) {
+        self.clients.retain(|s| *s != session);
+        if connected {
+            self.clients.push(session);
+        }
+        self.connected
+            .store(!self.clients.is_empty(), Ordering::Relaxed);
+    }
+
+    fn save_settings(&mut self) {
+        self.last_save = Instant::now();
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        if let Some(renderer) = &self.renderer {
+            renderer.save_layout(&mut self.state);
+        }
+        match self.state.save(path) {
+            Ok(()) => tracing::info!("Settings are saved to {}", path.display()),
+            Err(e) => tracing::warn!("Failed to save settings file ({}): {}", path.display(), e),
+        }
+    }
+
+    fn autosave_due(&self) -> bool {
+        self.state.autosave_interval > 0
+            && self.last_save.elapsed() >= Duration::from_secs(self.state.autosave_interval)
+    }
+
+    fn reload_settings(&mut self) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        let state: State = match File::open(path)
+            .map_err(crate::error::SimulatorError::from)
+            .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+        {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::warn!("Failed to reload settings file ({}): {}", path.display(), e);
+                return;
+            }
+        };
+        tracing::info!("Settings file ({}) is reloaded", path.display());
+
+        self.state.reload(state);
+        self.update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
+        self.update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
+        self.update_flag.set(UpdateFlag::UPDATE_SLICE_SIZE, true);
+        self.update_flag
+            .set(UpdateFlag::UPDATE_SLICE_COLOR_MAP, true);
+        self.update_flag.set(UpdateFlag::UPDATE_BACKGROUND, true);
+        self.update_flag.set(UpdateFlag::UPDATE_MARKERS, true);
+        self.update_flag.set(UpdateFlag::UPDATE_PRESENT_MODE, true);
+        if self.emulator.initialized() {
+            if !self.emulator.set_device_states(&self.state.devices) {
+                tracing::warn!(
+                    "The number of devices in the settings file does not match the connected devices, so the device settings are not reloaded"
+                );
+                self.state.devices = self.emulator.device_states();
+            }
+            self.update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+            self.update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
+            self.update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+        }
+    }
+
     fn update(&mut self, event: Option<&UserEvent>) {
         let system_time = self.state.system_time();
         self.emulator.update(system_time);
 
-        if let Some(UserEvent::Server(signal)) = event {
-            match signal {
-                crate::event::Signal::ConfigGeometry(geometry) => {
-                    self.emulator.initialize(geometry);
-                    self.renderer.as_mut().unwrap().initialize(&self.emulator);
-
-                    self.update_flag.set(UpdateFlag::UPDATE_CAMERA, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_TRANS_POS, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_SLICE_SIZE, true);
-                    self.update_flag
-                        .set(UpdateFlag::UPDATE_SLICE_COLOR_MAP, true);
-                    self.update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
-                }
-                crate::event::Signal::UpdateGeometry(geometry) => {
-                    self.emulator.update_geometry(geometry);
+        if let Some(UserEvent::SettingsChanged) = event {
+            self.reload_settings();
+        }
 
-                    self.update_flag.set(UpdateFlag::UPDATE_TRANS_POS, true);
-                }
-                crate::event::Signal::Send(tx) => {
-                    self.emulator.send(tx);
+        if let Some(UserEvent::Server(session, signal)) = event {
+            self.on_signal(*session, signal);
+        }
 
-                    self.update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+        let queue = self.queue.clone();
+        queue.drain(|session, signal| self.on_signal(session, signal));
+        if let Some(dropped) = self.queue.dropped_report() {
+            tracing::warn!(
+                "{} frames were dropped in the last few seconds, the simulator cannot keep up with the client",
+                dropped
+            );
+        }
+    }
+
+    fn on_signal(&mut self, session: SessionId, signal: &Signal) {
+        match signal {
+            Signal::ConfigGeometry(geometry) => {
+                self.config_geometry(session, geometry);
+                self.set_client(session, true);
+            }
+            Signal::UpdateGeometry(geometry) => {
+                if self.emulator.update_geometry(session, geometry) {
+                    tracing::info!(
+                        "Number of devices is changed to {}",
+                        self.emulator.num_devices()
+                    );
+                    self.reinitialize_renderer();
                 }
-                crate::event::Signal::Close => {
+
+                self.update_flag.set(UpdateFlag::UPDATE_TRANS_POS, true);
+            }
+            Signal::Send(tx) => {
+                self.emulator.send(session, tx);
+
+                self.update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+            }
+            Signal::Close => {
+                self.set_client(session, false);
+                let removed = self.emulator.remove(session);
+                if self.emulator.initialized() {
+                    if removed {
+                        self.reinitialize_renderer();
+                        tracing::info!(
+                            "Client {} is closed, {} clients remaining",
+                            session.map_or("-".to_string(), |addr| addr.to_string()),
+                            self.emulator.num_sessions()
+                        );
+                    }
+                } else {
                     self.emulator.clear();
+                    self.num_devices.store(0, Ordering::Relaxed);
                     tracing::info!("Server is closed by client");
-                    tracing::info!(
-                        "Waiting for client connection on http://0.0.0.0:{}",
-                        self.state.port
-                    );
+                    tracing::info!("Waiting for client connection on {}", self.state.address());
                 }
             }
         }
     }
 
+    fn reinitialize_renderer(&mut self) {
+        self.num_devices
+            .store(self.emulator.num_devices(), Ordering::Relaxed);
+        if let Some(renderer) = &mut self.renderer {
+            renderer.initialize(&self.emulator);
+        }
+
+        self.update_flag.set(UpdateFlag::UPDATE_TRANS_POS, true);
+        self.update_flag.set(UpdateFlag::UPDATE_TRANS_ALPHA, true);
+        self.update_flag.set(UpdateFlag::UPDATE_TRANS_STATE, true);
+        self.update_flag.set(UpdateFlag::UPDATE_SLICE_POS, true);
+        self.update_flag.set(UpdateFlag::UPDATE_SLICE_SIZE, true);
+        self.update_flag
+            .set(UpdateFlag::UPDATE_SLICE_COLOR_MAP, true);
+        self.update_flag.set(UpdateFlag::UPDATE_CONFIG, true);
+        self.update_flag.set(UpdateFlag::UPDATE_MARKERS, true);
+    }
+
+    fn throttled(&self) -> bool {
+        self.state.throttle_unfocused && !self.focused
+    }
+
+    fn throttle(&self, event_result: EventResult) -> EventResult {
+        if !self.throttled() {
+            return event_result;
+        }
+        let next = self.last_paint_time + UNFOCUSED_FRAME_INTERVAL;
+        match event_result {
+            EventResult::RepaintNow | EventResult::RepaintNext => EventResult::RepaintAt(next),
+            EventResult::RepaintAt(when) => EventResult::RepaintAt(when.max(next)),
+            r => r,
+        }
+    }
+
     fn run_ui_and_paint(&mut self, window: &Window) -> Result<EventResult> {
+        self.last_paint_time = Instant::now();
+        if std::mem::take(&mut self.state.save_request) || self.autosave_due() {
+            self.save_settings();
+        }
+        let throttled = self.throttled();
         let Self {
             renderer,
             state,
@@ -165,6 +578,13 @@ impl Simulator {
             ..
         } = self;
 
+        if state.left_handed != transform::left_handed() {
+            transform::set_left_handed(state.left_handed);
+            emulator.flip_handedness();
+            update_flag.set(UpdateFlag::UPDATE_TRANS_POS, true);
+            update_flag.set(UpdateFlag::UPDATE_MARKERS, true);
+        }
+
         if let Some(renderer) = renderer {
             if update_flag.contains(UpdateFlag::UPDATE_CAMERA) {
                 renderer.update_camera(state, window);
@@ -185,21 +605,25 @@ impl Simulator {
 
                     update_flag.remove(UpdateFlag::UPDATE_TRANS_STATE);
                 }
-                renderer.update_color(emulator);
+                renderer.update_color(emulator, state);
                 update_flag.remove(UpdateFlag::UPDATE_TRANS_ALPHA);
             }
 
             if update_flag.contains(UpdateFlag::UPDATE_SLICE_POS)
                 | update_flag.contains(UpdateFlag::UPDATE_SLICE_SIZE)
             {
+                state.slice.clamp_size();
                 renderer.update_slice(state);
                 update_flag.remove(UpdateFlag::UPDATE_SLICE_POS);
                 update_flag.remove(UpdateFlag::UPDATE_SLICE_SIZE);
             }
 
-            if update_flag.contains(UpdateFlag::UPDATE_CONFIG) {
+            if update_flag.contains(UpdateFlag::UPDATE_CONFIG)
+                | update_flag.contains(UpdateFlag::UPDATE_SLICE_FIELD)
+            {
                 renderer.update_config(state, emulator);
                 update_flag.remove(UpdateFlag::UPDATE_CONFIG);
+                update_flag.remove(UpdateFlag::UPDATE_SLICE_FIELD);
             }
 
             if update_flag.contains(UpdateFlag::UPDATE_SLICE_COLOR_MAP) {
@@ -207,12 +631,38 @@ impl Simulator {
                 update_flag.remove(UpdateFlag::UPDATE_SLICE_COLOR_MAP);
             }
 
+            if update_flag.contains(UpdateFlag::UPDATE_PRESENT_MODE) {
+                renderer.update_present_mode(state);
+                update_flag.remove(UpdateFlag::UPDATE_PRESENT_MODE);
+            }
+
+            if update_flag.contains(UpdateFlag::UPDATE_BACKGROUND) {
+                renderer.update_background(state);
+                update_flag.remove(UpdateFlag::UPDATE_BACKGROUND);
+            }
+
+            if update_flag.contains(UpdateFlag::UPDATE_MARKERS) {
+                renderer.update_markers(state);
+                update_flag.remove(UpdateFlag::UPDATE_MARKERS);
+            }
+
+            if std::mem::take(&mut state.snapshot.request) {
+                renderer.take_snapshot();
+            }
+
+            if std::mem::take(&mut state.save_image) {
+                match renderer.save_image(state, emulator, window) {
+                    Ok(path) => tracing::info!("Image is saved to {}", path.display()),
+                    Err(e) => tracing::error!("Failed to save image: {}", e),
+                }
+            }
+
             assert!(update_flag.is_empty());
 
             let result = renderer.run_ui_and_paint(state, emulator, window, update_flag)?;
 
             if emulator.initialized() && state.auto_play {
-                if cfg!(target_os = "windows") {
+                if cfg!(target_os = "windows") && !throttled {
                     window.request_redraw();
                 } else {
                     return Ok(EventResult::RepaintNow);
@@ -238,6 +688,7 @@ impl Simulator {
             match event {
                 winit::event::WindowEvent::RedrawRequested => self.run_ui_and_paint(&window),
                 _ => {
+                    self.track_window(&window, &event);
                     if let Some(renderer) = &mut self.renderer {
                         Ok(renderer.on_window_event(&event, &window, &self.state))
                     } else {
@@ -250,7 +701,31 @@ impl Simulator {
         }
     }
 
+    fn track_window(&mut self, window: &Window, event: &winit::event::WindowEvent) {
+        match event {
+            winit::event::WindowEvent::Resized(size) => {
+                self.state.maximized = window.is_maximized();
+                if !self.state.maximized && size.width > 0 && size.height > 0 {
+                    let size = size.to_logical::<u32>(window.scale_factor());
+                    self.state.window_size = (size.width, size.height);
+                }
+            }
+            winit::event::WindowEvent::Moved(pos) if !window.is_maximized() => {
+                self.state.window_position = Some((pos.x, pos.y));
+            }
+            winit::event::WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+            }
+            _ => {}
+        }
+    }
+
     fn on_user_event(&mut self, event: UserEvent) -> Result<EventResult> {
+        if let UserEvent::FieldQuery { points, result } = event {
+            self.update(None);
+            self.answer_field_query(&points, result);
+            return Ok(EventResult::Wait);
+        }
         self.update(Some(&event));
         if let Some(renderer) = &mut self.renderer {
             return Ok(renderer.on_user_event(&event));
@@ -274,6 +749,7 @@ impl Simulator {
     ) {
         let mut exit = false;
 
+        let event_result = event_result.map(|event_result| self.throttle(event_result));
         let combined_result = event_result.and_then(|event_result| match event_result {
             EventResult::Wait => {
                 event_loop.set_control_flow(ControlFlow::Wait);
@@ -380,6 +856,17 @@ impl ApplicationHandler<UserEvent> for Simulator {
     }
 
     fn exiting(&mut self, _: &winit::event_loop::ActiveEventLoop) {
+        if let Some(renderer) = &self.renderer {
+            renderer.save_layout(&mut self.state);
+        }
+        self.shutdown();
+    }
+}
+
+impl Simulator {
+    fn shutdown(&mut self) {
+        // release the requests waiting for their frames, otherwise the server waits for them forever
+        self.queue.close();
         if let Some(server) = self.server.take() {
             tracing::info!("Shutting down server...");
             let r = self.runtime.block_on(server.shutdown());
@@ -389,5 +876,95 @@ impl ApplicationHandler<UserEvent> for Simulator {
                 tracing::info!("Shutting down server...done");
             }
         }
+        if let Some(health_server) = self.health_server.take() {
+            if let Err(err) = self.runtime.block_on(health_server.shutdown()) {
+                tracing::error!("Failed to shutdown health server: {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use autd3_driver::defined::mm;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::geometry_file::grid_geometry;
+
+    #[test]
+    fn headless_field_query() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut app = Simulator::new(
+            State {
+                port: 0,
+                auto_play: false,
+                ..Default::default()
+            },
+            RunOptions {
+                preview_geometry: Some(grid_geometry(1, 1)),
+                health_port: Some(0),
+                ..Default::default()
+            },
+            EventProxy::Channel(sender),
+            None,
+        )
+        .unwrap();
+        app.config_preview_geometry();
+        assert!(app.headless_step(&receiver));
+        app.emulator.iter_mut().for_each(|emulator| {
+            emulator.transducers.iter_mut().for_each(|tr| tr.amp = 1.);
+        });
+
+        let point = Vector3::new(0., 0., 150. * mm);
+        let port = app.health_server.as_ref().unwrap().port();
+        let response = app.runtime.spawn(async move {
+            let body = serde_json::json!({ "points": [point] }).to_string();
+            let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .unwrap();
+            stream
+                .write_all(
+                    format!(
+                        "POST /field HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        });
+        let start = Instant::now();
+        while !response.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            assert!(app.headless_step(&receiver));
+        }
+        let response = app.runtime.block_on(response).unwrap();
+        let (status, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(status.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        let transducers = app.emulator.transducers();
+        let positions = transducers
+            .positions()
+            .iter()
+            .map(|p| p.truncate())
+            .collect::<Vec<_>>();
+        let expected = pressure_at(
+            &positions,
+            transducers.states(),
+            app.state.sound_speed,
+            &[point],
+        );
+        assert!(expected[0] > 0.);
+        assert_eq!(
+            serde_json::json!({ "pressure": expected }),
+            serde_json::from_str::<serde_json::Value>(body).unwrap()
+        );
+
+        app.shutdown();
     }
 }
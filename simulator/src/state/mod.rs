@@ -3,12 +3,27 @@ use autd3_driver::{
     ethercat::{DcSysTime, ECAT_DC_SYS_TIME_BASE},
 };
 
+use std::path::{Path, PathBuf};
+
 use glam::EulerRot;
 use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+
+use crate::{
+    common::{
+        color::{Color, Hsv},
+        color_map::ColorMap,
+    },
+    Quaternion, Vector2, Vector3, Vector4, ZPARITY,
+};
 
-use crate::{common::color_map::ColorMap, Quaternion, Vector2, Vector3, ZPARITY};
+const DEFAULT_DENSITY: f32 = 1.225;
+const DEFAULT_INTENSITY_MAX: f32 = 30000.;
+pub const LAYOUT_FILE: &str = "layout.json";
+pub const DEFAULT_FONT_SIZE: f32 = 12.5;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct CameraState {
     pub pos: Vector3,
     pub rot: Vector3,
@@ -16,9 +31,15 @@ pub struct CameraState {
     pub near_clip: f32,
     pub far_clip: f32,
     pub move_speed: f32,
+    pub zoom_sensitivity: f32,
+    pub invert_zoom: bool,
 }
 
 impl CameraState {
+    const fn default_zoom_sensitivity() -> f32 {
+        1.
+    }
+
     pub fn rotation(&self) -> Quaternion {
         Quaternion::from_euler(
             EulerRot::XYZ,
@@ -27,18 +48,63 @@ impl CameraState {
             self.rot.z.to_radians(),
         )
     }
+
+    pub fn fit(&mut self, positions: &[Vector4], zparity: f32) {
+        if positions.is_empty() {
+            return;
+        }
+        let (min, max) = positions.iter().fold(
+            (Vector3::splat(f32::MAX), Vector3::splat(f32::MIN)),
+            |(min, max), p| (min.min(p.truncate()), max.max(p.truncate())),
+        );
+        let center = (min + max) / 2.;
+        let extent = max - min;
+        let radius = 0.5 * extent.x.max(extent.y) * 1.1;
+        let distance = radius / (self.fov.to_radians() / 2.).tan() + extent.z / 2.;
+        self.pos = center + Vector3::new(0., 0., distance * zparity);
+        self.rot = Vector3::new(90. - 90. * zparity, 0., 0.);
+        self.far_clip = self.far_clip.max(distance * 2.);
+    }
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        Self {
+            pos: Vector3::new(86.6252 * mm, -533.2867 * mm, 150.0 * mm * ZPARITY),
+            rot: Vector3::new(90.0 * ZPARITY, 0., 0.),
+            fov: 45.,
+            near_clip: 0.1 * mm,
+            far_clip: 1000. * mm,
+            move_speed: 1. * mm,
+            zoom_sensitivity: Self::default_zoom_sensitivity(),
+            invert_zoom: false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct SliceState {
     pub pos: Vector3,
     pub rot: Vector3,
     pub size: Vector2,
+    pub pixel_size: f32,
     pub color_map: ColorMap,
     pub pressure_max: f32,
+    pub field: SliceField,
+    pub intensity_max: f32,
+    pub mask: bool,
+    /// Pixels where the displayed field is below this value are transparent
+    pub mask_threshold: f32,
+    pub carpet: bool,
+    pub carpet_height: f32,
 }
 
 impl SliceState {
+    fn default_carpet_height() -> f32 {
+        50. * mm
+    }
+
     pub fn rotation(&self) -> Quaternion {
         Quaternion::from_euler(
             EulerRot::XYZ,
@@ -47,6 +113,220 @@ impl SliceState {
             self.rot.z.to_radians(),
         )
     }
+
+    pub const MIN_SIZE: f32 = 1. * mm;
+    pub const MAX_SIZE: f32 = 1024. * mm;
+    pub const MIN_PIXEL_SIZE: f32 = 0.1 * mm;
+    pub const MAX_PIXEL_SIZE: f32 = 10. * mm;
+    /// Maximum number of pixels along each side, i.e., the size of the field texture
+    pub const MAX_RESOLUTION: u32 = 1024;
+
+    /// Size of the pixels actually computed, which is larger than `pixel_size` if the slice needs more than [`Self::MAX_RESOLUTION`] pixels along a side
+    pub fn effective_pixel_size(&self) -> Vector2 {
+        Vector2::splat(self.pixel_size).max(self.size / Self::MAX_RESOLUTION as f32)
+    }
+
+    pub fn clamp_size(&mut self) {
+        let clamp = |v: f32| {
+            if v.is_nan() {
+                Self::MIN_SIZE
+            } else {
+                v.clamp(Self::MIN_SIZE, Self::MAX_SIZE)
+            }
+        };
+        self.size.x = clamp(self.size.x);
+        self.size.y = clamp(self.size.y);
+        self.pixel_size = if self.pixel_size.is_nan() {
+            Self::MIN_PIXEL_SIZE
+        } else {
+            self.pixel_size
+                .clamp(Self::MIN_PIXEL_SIZE, Self::MAX_PIXEL_SIZE)
+        };
+    }
+}
+
+impl Default for SliceState {
+    fn default() -> Self {
+        Self {
+            pos: Vector3::new(86.6252 * mm, 66.7133 * mm, 150.0 * mm * ZPARITY),
+            rot: Vector3::new(90.0 * ZPARITY, 0., 0.),
+            size: Vector2::new(300.0 * mm, 300.0 * mm),
+            pixel_size: 1. * mm,
+            color_map: ColorMap::Inferno,
+            pressure_max: 5000.,
+            field: SliceField::default(),
+            intensity_max: DEFAULT_INTENSITY_MAX,
+            mask: false,
+            mask_threshold: 1000.,
+            carpet: false,
+            carpet_height: Self::default_carpet_height(),
+        }
+    }
+}
+
+/// Quantity shown on the slice
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Serialize, Deserialize)]
+pub enum SliceField {
+    /// Amplitude of the acoustic pressure [Pa]
+    #[default]
+    Pressure,
+    /// Time-averaged intensity `p^2/(2ρc)` [W/m^2]
+    Intensity,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MeasureState {
+    pub enable: bool,
+    pub points: Vec<Vector3>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectionState {
+    pub device: Option<usize>,
+    pub scroll: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SnapshotState {
+    pub request: bool,
+    pub taken: bool,
+    pub show_diff: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct HudState {
+    pub enable: bool,
+    pub opacity: f32,
+    pub position: Option<(f32, f32)>,
+}
+
+impl Default for HudState {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            opacity: 0.8,
+            position: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DeviceState {
+    pub visible: bool,
+    pub alpha: f32,
+    pub enable: bool,
+    pub mod_enable: bool,
+    pub thermal: bool,
+}
+
+impl DeviceState {
+    const fn default_alpha() -> f32 {
+        1.
+    }
+
+    pub fn effective_alpha(&self) -> f32 {
+        if self.visible {
+            self.alpha
+        } else {
+            0.
+        }
+    }
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            alpha: Self::default_alpha(),
+            enable: true,
+            mod_enable: true,
+            thermal: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum TimeUnit {
+    #[default]
+    Ns,
+    Us,
+    Ms,
+}
+
+impl TimeUnit {
+    pub const fn scale(&self) -> i32 {
+        match self {
+            Self::Ns => 1,
+            Self::Us => 1_000,
+            Self::Ms => 1_000_000,
+        }
+    }
+
+    pub const fn suffix(&self) -> &'static str {
+        match self {
+            Self::Ns => "ns",
+            Self::Us => "µs",
+            Self::Ms => "ms",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Serialize, Deserialize, EnumIter)]
+pub enum UpAxis {
+    Y,
+    #[default]
+    Z,
+}
+
+impl UpAxis {
+    /// Rotation from the scene to the display frame, in which the camera treats z as up
+    pub fn rotation(&self) -> Quaternion {
+        match self {
+            UpAxis::Y => Quaternion::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            UpAxis::Z => Quaternion::IDENTITY,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Serialize, Deserialize, EnumIter)]
+pub enum BackgroundMode {
+    #[default]
+    Solid,
+    Gradient,
+    Image,
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Serialize, Deserialize, EnumIter)]
+pub enum TransducerColoring {
+    #[default]
+    PhaseHsv,
+    AmplitudeGray,
+    EnableMask,
+}
+
+pub fn default_tint(dev: usize) -> egui::Color32 {
+    let [r, g, b, _] = Hsv {
+        h: dev as f32 * 0.618034,
+        s: 0.5,
+        v: 1.,
+        a: 1.,
+    }
+    .rgba();
+    egui::Color32::from_rgb((r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8)
+}
+
+fn default_eye_separation() -> f32 {
+    65. * mm
+}
+
+const fn default_left_handed() -> bool {
+    cfg!(feature = "left_handed")
+}
+
+fn default_background_bottom() -> egui::Color32 {
+    egui::Color32::from_rgb(20, 20, 20)
 }
 
 #[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
@@ -59,59 +339,126 @@ pub enum Tab {
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
     pub window_size: (u32, u32),
+    pub left_handed: bool,
+    pub window_position: Option<(i32, i32)>,
+    pub maximized: bool,
     pub ui_scale: f32,
+    pub font_path: String,
+    pub font_size: f32,
     pub camera: CameraState,
+    pub up_axis: UpAxis,
+    pub lock_view: bool,
     pub slice: SliceState,
     pub sound_speed: f32,
+    /// Density of the medium [kg/m^3]
+    pub density: f32,
     pub background: egui::Color32,
+    pub background_mode: BackgroundMode,
+    pub background_bottom: egui::Color32,
+    pub background_image: String,
+    pub transparent_background: bool,
+    pub capture_size: Option<(u32, u32)>,
     pub mod_enable: bool,
     pub auto_play: bool,
     pub real_time: u64,
     pub time_scale: f32,
     pub port: u16,
+    pub uds: Option<String>,
     pub lightweight: bool,
     pub vsync: bool,
+    pub throttle_unfocused: bool,
+    pub msaa: u32,
     pub settings_dir: String,
+    pub layout_dir: Option<String>,
+    pub autosave_interval: u64,
     pub time_step: i32,
+    pub time_step_unit: TimeUnit,
+    pub raw_units: bool,
+    pub export_dir: String,
     pub debug: bool,
     pub tab: Tab,
+    pub devices: Vec<DeviceState>,
+    pub coloring: TransducerColoring,
+    pub tint: bool,
+    pub tints: Vec<egui::Color32>,
+    pub legend: bool,
+    pub hud: HudState,
+    pub anaglyph: bool,
+    pub eye_separation: f32,
+    pub markers: Vec<Vector3>,
+    #[serde(skip)]
+    pub measure: MeasureState,
+    #[serde(skip)]
+    pub selection: SelectionState,
+    #[serde(skip)]
+    pub snapshot: SnapshotState,
+    #[serde(skip)]
+    pub save_request: bool,
+    #[serde(skip)]
+    pub save_image: bool,
+    #[serde(skip)]
+    pub hide_ui: bool,
 }
 
 impl std::default::Default for State {
     fn default() -> Self {
         Self {
             window_size: (800, 600),
+            left_handed: default_left_handed(),
+            window_position: None,
+            maximized: false,
             ui_scale: 1.0,
-            camera: CameraState {
-                pos: Vector3::new(86.6252 * mm, -533.2867 * mm, 150.0 * mm * ZPARITY),
-                rot: Vector3::new(90.0 * ZPARITY, 0., 0.),
-                fov: 45.,
-                near_clip: 0.1 * mm,
-                far_clip: 1000. * mm,
-                move_speed: 1. * mm,
-            },
-            slice: SliceState {
-                pos: Vector3::new(86.6252 * mm, 66.7133 * mm, 150.0 * mm * ZPARITY),
-                rot: Vector3::new(90.0 * ZPARITY, 0., 0.),
-                size: Vector2::new(300.0 * mm, 300.0 * mm),
-                color_map: ColorMap::Inferno,
-                pressure_max: 5000.,
-            },
+            font_path: String::new(),
+            font_size: DEFAULT_FONT_SIZE,
+            camera: CameraState::default(),
+            up_axis: UpAxis::default(),
+            lock_view: false,
+            slice: SliceState::default(),
             background: egui::Color32::from_rgb(60, 60, 60),
+            background_mode: BackgroundMode::default(),
+            background_bottom: default_background_bottom(),
+            background_image: String::new(),
+            transparent_background: false,
+            capture_size: None,
             sound_speed: 340.0e3 * mm,
+            density: DEFAULT_DENSITY,
             mod_enable: false,
             auto_play: true,
             real_time: DcSysTime::now().sys_time(),
             time_scale: 1.0,
             port: 8080,
+            uds: None,
             lightweight: false,
             vsync: true,
+            throttle_unfocused: false,
+            msaa: 4,
             settings_dir: String::new(),
+            layout_dir: None,
+            autosave_interval: 0,
             time_step: 1000000,
+            time_step_unit: TimeUnit::default(),
+            raw_units: false,
+            export_dir: String::new(),
             debug: false,
             tab: Tab::default(),
+            devices: Vec::new(),
+            coloring: TransducerColoring::default(),
+            tint: false,
+            tints: Vec::new(),
+            legend: false,
+            hud: HudState::default(),
+            anaglyph: false,
+            eye_separation: default_eye_separation(),
+            markers: Vec::new(),
+            measure: MeasureState::default(),
+            selection: SelectionState::default(),
+            snapshot: SnapshotState::default(),
+            save_request: false,
+            save_image: false,
+            hide_ui: false,
         }
     }
 }
@@ -122,6 +469,29 @@ impl State {
             .unwrap()
     }
 
+    pub fn address(&self) -> String {
+        match &self.uds {
+            Some(path) => format!("unix:{}", path),
+            None => format!("http://0.0.0.0:{}", self.port),
+        }
+    }
+
+    pub fn layout_path(&self) -> PathBuf {
+        Path::new(&self.settings_dir).join(LAYOUT_FILE)
+    }
+
+    /// Write the settings to a temporary file first and then rename it, so that the file is never left half-written
+    pub fn save(&self, path: impl AsRef<Path>) -> crate::error::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
     pub fn background(&self) -> wgpu::Color {
         wgpu::Color {
             r: self.background[0] as f64 / 255.,
@@ -131,20 +501,295 @@ impl State {
         }
     }
 
+    pub fn capture_background(&self) -> wgpu::Color {
+        if self.transparent_background {
+            wgpu::Color::TRANSPARENT
+        } else {
+            self.background()
+        }
+    }
+
+    pub fn tint(&self, dev: usize) -> egui::Color32 {
+        self.tints
+            .get(dev)
+            .copied()
+            .unwrap_or_else(|| default_tint(dev))
+    }
+
+    pub fn zparity(&self) -> f32 {
+        if self.left_handed {
+            -1.
+        } else {
+            1.
+        }
+    }
+
+    /// Switch the coordinate handedness, keeping the camera and the slice at the same place on screen
+    pub fn set_left_handed(&mut self, left_handed: bool) {
+        if self.left_handed == left_handed {
+            return;
+        }
+        self.left_handed = left_handed;
+        [
+            (&mut self.camera.pos, &mut self.camera.rot),
+            (&mut self.slice.pos, &mut self.slice.rot),
+        ]
+        .into_iter()
+        .for_each(|(pos, rot)| {
+            pos.z = -pos.z;
+            rot.x = -rot.x;
+            rot.y = -rot.y;
+        });
+        self.markers.iter_mut().for_each(|p| p.z = -p.z);
+    }
+
+    /// Fields that are not reloaded from the settings file while running.
+    /// The window, the server address and the settings directory are fixed at startup, `debug` is always given on the command line, and `real_time` is the clock of the running simulation.
+    pub const NOT_RELOADED: &'static [&'static str] = &[
+        "window_size",
+        "window_position",
+        "maximized",
+        "port",
+        "uds",
+        "lightweight",
+        "settings_dir",
+        "debug",
+        "real_time",
+    ];
+
+    /// Copy all fields except [`Self::NOT_RELOADED`] and the ones marked `#[serde(skip)]`.
+    /// `msaa` takes effect after restart, as when it is changed in the UI.
+    pub fn reload(&mut self, state: State) {
+        self.left_handed = state.left_handed;
+        self.ui_scale = state.ui_scale;
+        self.font_path = state.font_path;
+        self.font_size = state.font_size;
+        self.camera = state.camera;
+        self.up_axis = state.up_axis;
+        self.lock_view = state.lock_view;
+        self.slice = state.slice;
+        self.sound_speed = state.sound_speed;
+        self.density = state.density;
+        self.background = state.background;
+        self.background_mode = state.background_mode;
+        self.background_bottom = state.background_bottom;
+        self.background_image = state.background_image;
+        self.transparent_background = state.transparent_background;
+        self.capture_size = state.capture_size;
+        self.mod_enable = state.mod_enable;
+        self.auto_play = state.auto_play;
+        self.time_scale = state.time_scale;
+        self.vsync = state.vsync;
+        self.throttle_unfocused = state.throttle_unfocused;
+        self.msaa = state.msaa;
+        self.layout_dir = state.layout_dir;
+        self.autosave_interval = state.autosave_interval;
+        self.time_step = state.time_step;
+        self.time_step_unit = state.time_step_unit;
+        self.raw_units = state.raw_units;
+        self.export_dir = state.export_dir;
+        self.tab = state.tab;
+        self.devices = state.devices;
+        self.coloring = state.coloring;
+        self.tint = state.tint;
+        self.tints = state.tints;
+        self.legend = state.legend;
+        self.hud = state.hud;
+        self.anaglyph = state.anaglyph;
+        self.eye_separation = state.eye_separation;
+        self.markers = state.markers;
+    }
+
     pub fn merge(&mut self, state: State) {
+        self.left_handed = state.left_handed;
         self.window_size = state.window_size;
+        self.window_position = state.window_position;
+        self.maximized = state.maximized;
         self.ui_scale = state.ui_scale;
+        self.font_path = state.font_path;
+        self.font_size = state.font_size;
         self.camera = state.camera;
+        self.up_axis = state.up_axis;
+        self.lock_view = state.lock_view;
         self.slice = state.slice;
         self.sound_speed = state.sound_speed;
+        self.density = state.density;
         self.background = state.background;
+        self.background_mode = state.background_mode;
+        self.background_bottom = state.background_bottom;
+        self.background_image = state.background_image;
+        self.transparent_background = state.transparent_background;
+        self.capture_size = state.capture_size;
         self.mod_enable = state.mod_enable;
         self.auto_play = state.auto_play;
         self.time_scale = state.time_scale;
         self.port = state.port;
+        self.uds = state.uds;
         self.lightweight = state.lightweight;
         self.vsync = state.vsync;
+        self.throttle_unfocused = state.throttle_unfocused;
+        self.msaa = state.msaa;
         self.settings_dir = state.settings_dir;
+        self.layout_dir = state.layout_dir;
+        self.autosave_interval = state.autosave_interval;
+        self.time_step = state.time_step;
+        self.time_step_unit = state.time_step_unit;
+        self.raw_units = state.raw_units;
+        self.export_dir = state.export_dir;
         self.debug = state.debug;
+        self.devices = state.devices;
+        self.coloring = state.coloring;
+        self.tint = state.tint;
+        self.tints = state.tints;
+        self.legend = state.legend;
+        self.hud = state.hud;
+        self.anaglyph = state.anaglyph;
+        self.eye_separation = state.eye_separation;
+        self.markers = state.markers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_partial() {
+        let state: State = serde_json::from_str(
+            r#"{
+                "port": 9090,
+                "real_time": 0,
+                "camera": { "fov": 60.0 },
+                "slice": { "field": "Intensity", "mask_threshold": 10.0 },
+                "devices": [{ "alpha": 0.5 }],
+                "unknown": true
+            }"#,
+        )
+        .unwrap();
+
+        let mut expected = State {
+            port: 9090,
+            real_time: 0,
+            devices: vec![DeviceState {
+                alpha: 0.5,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        expected.camera.fov = 60.;
+        expected.slice.field = SliceField::Intensity;
+        expected.slice.mask_threshold = 10.;
+
+        assert_eq!(
+            serde_json::to_value(&expected).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn reload_copies_non_startup_fields() {
+        let mut reloaded = State {
+            window_size: (1920, 1080),
+            left_handed: !default_left_handed(),
+            window_position: Some((10, 20)),
+            maximized: true,
+            ui_scale: 2.,
+            font_path: "font.ttf".to_string(),
+            font_size: 20.,
+            up_axis: UpAxis::Y,
+            lock_view: true,
+            sound_speed: 343.0e3 * mm,
+            density: 1000.,
+            background: egui::Color32::RED,
+            background_mode: BackgroundMode::Gradient,
+            background_bottom: egui::Color32::BLUE,
+            background_image: "background.png".to_string(),
+            transparent_background: true,
+            capture_size: Some((640, 480)),
+            mod_enable: true,
+            auto_play: false,
+            real_time: 1,
+            time_scale: 2.,
+            port: 9090,
+            uds: Some("autd3.sock".to_string()),
+            lightweight: true,
+            vsync: false,
+            throttle_unfocused: true,
+            msaa: 8,
+            settings_dir: "settings".to_string(),
+            layout_dir: Some("layout".to_string()),
+            autosave_interval: 60,
+            time_step: 1000,
+            time_step_unit: TimeUnit::Us,
+            raw_units: true,
+            export_dir: "export".to_string(),
+            debug: true,
+            tab: Tab::Info,
+            devices: vec![DeviceState::default()],
+            coloring: TransducerColoring::EnableMask,
+            tint: true,
+            tints: vec![egui::Color32::GREEN],
+            legend: true,
+            hud: HudState {
+                enable: true,
+                ..Default::default()
+            },
+            anaglyph: true,
+            eye_separation: 70. * mm,
+            markers: vec![Vector3::ZERO],
+            ..Default::default()
+        };
+        reloaded.camera.fov = 60.;
+        reloaded.slice.mask = true;
+
+        let mut state = State::default();
+        let initial = serde_json::to_value(&state).unwrap();
+        let reloaded_value = serde_json::to_value(&reloaded).unwrap();
+        state.reload(reloaded);
+        let state = serde_json::to_value(&state).unwrap();
+
+        initial
+            .as_object()
+            .unwrap()
+            .iter()
+            .for_each(|(field, initial)| {
+                // every field must be changed so that a field missing in reload is detected
+                assert_ne!(initial, &reloaded_value[field], "{} is not changed", field);
+                if State::NOT_RELOADED.contains(&field.as_str()) {
+                    assert_eq!(initial, &state[field], "{} is reloaded", field);
+                } else {
+                    assert_eq!(
+                        reloaded_value[field], state[field],
+                        "{} is not reloaded",
+                        field
+                    );
+                }
+            });
+    }
+
+    #[test]
+    fn effective_pixel_size() {
+        let slice = SliceState {
+            size: Vector2::new(100. * mm, SliceState::MAX_SIZE),
+            pixel_size: 0.5 * mm,
+            ..Default::default()
+        };
+        assert_eq!(
+            Vector2::new(
+                0.5 * mm,
+                SliceState::MAX_SIZE / SliceState::MAX_RESOLUTION as f32
+            ),
+            slice.effective_pixel_size()
+        );
+    }
+
+    #[test]
+    fn tint_defaults_for_new_devices() {
+        let state = State {
+            tints: vec![egui::Color32::RED],
+            ..Default::default()
+        };
+        assert_eq!(egui::Color32::RED, state.tint(0));
+        assert_eq!(default_tint(1), state.tint(1));
     }
 }
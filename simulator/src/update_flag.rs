@@ -11,5 +11,12 @@ bitflags::bitflags! {
         const UPDATE_TRANS_POS = 1 << 6;
 
         const UPDATE_CONFIG = 1 << 7;
+        const UPDATE_SLICE_FIELD = 1 << 8;
+
+        const UPDATE_PRESENT_MODE = 1 << 9;
+
+        const UPDATE_BACKGROUND = 1 << 10;
+
+        const UPDATE_MARKERS = 1 << 11;
     }
 }
@@ -0,0 +1,61 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::event::{EventProxy, UserEvent};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub struct SettingsWatcher {
+    stop: Arc<AtomicBool>,
+    th: Option<JoinHandle<()>>,
+}
+
+impl SettingsWatcher {
+    pub fn new(path: PathBuf, proxy: EventProxy) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let th = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let modified =
+                    |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                let mut last = modified(&path);
+                let mut pending: Option<Instant> = None;
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(POLL_INTERVAL);
+                    let current = modified(&path);
+                    if current != last {
+                        last = current;
+                        pending = Some(Instant::now());
+                        continue;
+                    }
+                    if let Some(changed_at) = pending {
+                        if changed_at.elapsed() >= DEBOUNCE {
+                            pending = None;
+                            if proxy.send_event(UserEvent::SettingsChanged).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        Self { stop, th: Some(th) }
+    }
+}
+
+impl Drop for SettingsWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(th) = self.th.take() {
+            let _ = th.join();
+        }
+    }
+}
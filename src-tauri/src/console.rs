@@ -0,0 +1,140 @@
+use std::{collections::VecDeque, fs::File, io::Write, path::PathBuf, sync::Mutex};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum ConsoleMessage {
+    Started(String),
+    Stdout(String),
+    Info(String),
+    Error(String),
+    Exit(Option<i32>),
+}
+
+impl ConsoleMessage {
+    pub fn text(&self) -> String {
+        match self {
+            ConsoleMessage::Started(name) => format!("{} is started", name),
+            ConsoleMessage::Stdout(s) | ConsoleMessage::Info(s) | ConsoleMessage::Error(s) => {
+                s.clone()
+            }
+            ConsoleMessage::Exit(Some(code)) => format!("Process exited with code {}", code),
+            ConsoleMessage::Exit(None) => "Process exited".to_string(),
+        }
+    }
+}
+
+pub const DEFAULT_CONSOLE_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsoleHistorySnapshot {
+    pub lines: Vec<String>,
+    pub dropped: usize,
+}
+
+fn timestamped(time: &DateTime<Local>, line: &str) -> String {
+    format!("[{}] {}", time.format("%Y-%m-%d %H:%M:%S%.3f"), line)
+}
+
+struct ConsoleLog {
+    path: PathBuf,
+    file: File,
+    lines: usize,
+}
+
+struct History {
+    capacity: usize,
+    lines: VecDeque<(DateTime<Local>, String)>,
+    dropped: usize,
+    log: Option<ConsoleLog>,
+}
+
+impl History {
+    fn truncate(&mut self) -> bool {
+        let n = self.lines.len().saturating_sub(self.capacity);
+        self.lines.drain(..n);
+        let first = n > 0 && self.dropped == 0;
+        self.dropped += n;
+        first
+    }
+}
+
+pub struct ConsoleHistory(Mutex<History>);
+
+impl ConsoleHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self(Mutex::new(History {
+            capacity,
+            lines: VecDeque::with_capacity(capacity),
+            dropped: 0,
+            log: None,
+        }))
+    }
+
+    /// Writes every line to the file as it arrives, so that the whole session can be saved
+    /// after the oldest lines are dropped from the history.
+    pub fn set_log_file(&self, path: PathBuf) -> std::io::Result<()> {
+        let mut history = self.0.lock().unwrap();
+        let mut file = File::create(&path)?;
+        for (time, line) in history.lines.iter() {
+            writeln!(file, "{}", timestamped(time, line))?;
+        }
+        let lines = history.lines.len();
+        history.log = Some(ConsoleLog { path, file, lines });
+        Ok(())
+    }
+
+    /// Returns the log file and the number of lines in it, if the lines are written to a file
+    pub fn log_file(&self) -> Option<(PathBuf, usize)> {
+        self.0
+            .lock()
+            .unwrap()
+            .log
+            .as_ref()
+            .map(|log| (log.path.clone(), log.lines))
+    }
+
+    /// Returns the number of dropped lines when the oldest lines start being dropped.
+    pub fn push(&self, line: String) -> Option<usize> {
+        let mut history = self.0.lock().unwrap();
+        let time = Local::now();
+        if let Some(mut log) = history.log.take() {
+            // stop logging if the file cannot be written, and fall back to the history
+            if writeln!(log.file, "{}", timestamped(&time, &line)).is_ok() {
+                log.lines += 1;
+                history.log = Some(log);
+            }
+        }
+        history.lines.push_back((time, line));
+        history.truncate().then_some(history.dropped)
+    }
+
+    pub fn set_capacity(&self, capacity: usize) -> Option<usize> {
+        let mut history = self.0.lock().unwrap();
+        history.capacity = capacity;
+        history.truncate().then_some(history.dropped)
+    }
+
+    pub fn snapshot(&self) -> ConsoleHistorySnapshot {
+        let history = self.0.lock().unwrap();
+        ConsoleHistorySnapshot {
+            lines: history.lines.iter().map(|(_, line)| line.clone()).collect(),
+            dropped: history.dropped,
+        }
+    }
+
+    /// Returns the lines in the history with their timestamps and the number of dropped lines
+    pub fn timestamped_lines(&self) -> (Vec<String>, usize) {
+        let history = self.0.lock().unwrap();
+        (
+            history
+                .lines
+                .iter()
+                .map(|(time, line)| timestamped(time, line))
+                .collect(),
+            history.dropped,
+        )
+    }
+}
@@ -1,22 +1,48 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod console;
 mod options;
 
-use options::Options;
+use console::{ConsoleHistory, ConsoleHistorySnapshot, ConsoleMessage, DEFAULT_CONSOLE_HISTORY};
+use options::{Options, SETTINGS_VERSION};
 
-use std::{path::PathBuf, process::Stdio};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Serialize;
 
 use tauri::{Emitter, Manager};
+use tauri_plugin_shell::{process::CommandEvent, ShellExt};
 
 use tokio::{
-    fs::{File, OpenOptions},
+    fs::File,
     io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
     process::Command,
-    sync::mpsc::{channel, Sender},
+    sync::{
+        mpsc::{channel, Sender},
+        oneshot,
+    },
 };
 
 const SETTINGS_PATH: &str = "settings.json";
+const PROFILES_DIR: &str = "profiles";
+const CONSOLE_LOG_PATH: &str = "console.log";
+const DEFAULT_PROFILE: &str = "default";
+const TWINCAT_SERVER: &str = "TwinCATAUTDServer";
+const SIDECAR_SERVERS: [&str; 4] = [
+    "SOEMAUTDServer",
+    "TwinCATAUTDServerLightweight",
+    "simulator",
+    "simulator-unity",
+];
 
 fn get_settings_file_path(handle: &tauri::AppHandle) -> std::io::Result<PathBuf> {
     let mut path = handle
@@ -31,21 +57,60 @@ fn get_settings_file_path(handle: &tauri::AppHandle) -> std::io::Result<PathBuf>
     Ok(path)
 }
 
+fn get_profiles_dir_path(handle: &tauri::AppHandle) -> std::io::Result<PathBuf> {
+    let path = get_settings_file_path(handle)?.with_file_name(PROFILES_DIR);
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path)
+}
+
+fn add_libpath(var: &str, libpaths: &[String], prepend: bool) {
+    let current = std::env::var(var).unwrap_or_default();
+    let entries = current
+        .split(':')
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let new_entries = libpaths
+        .iter()
+        .filter(|p| !p.is_empty() && !entries.contains(p))
+        .cloned()
+        .collect::<Vec<_>>();
+    if new_entries.is_empty() {
+        return;
+    }
+    let path = if prepend {
+        [new_entries, entries]
+    } else {
+        [entries, new_entries]
+    }
+    .concat()
+    .join(":");
+    std::env::set_var(var, path);
+}
+
 #[tauri::command]
-fn set_libpath(_: tauri::AppHandle) {
+fn set_libpath(handle: tauri::AppHandle, libpaths: Option<Vec<String>>) {
     if cfg!(target_os = "macos") {
-        let home = std::env::var("HOME").unwrap_or_default();
-        let libpath = format!("{}/lib:/usr/local/lib:/usr/lib", home);
-        let fallback_path = if let Ok(path) = std::env::var("DYLD_FALLBACK_LIBRARY_PATH") {
-            if path.contains(&libpath) {
-                path
-            } else {
-                format!("{}:{}", path, libpath)
-            }
-        } else {
-            libpath
-        };
-        std::env::set_var("DYLD_FALLBACK_LIBRARY_PATH", fallback_path);
+        let libpaths = libpaths.unwrap_or_else(|| {
+            let home = std::env::var("HOME").unwrap_or_default();
+            vec![
+                format!("{}/lib", home),
+                "/usr/local/lib".to_string(),
+                "/usr/lib".to_string(),
+            ]
+        });
+        add_libpath("DYLD_FALLBACK_LIBRARY_PATH", &libpaths, false);
+    } else if cfg!(target_os = "linux") {
+        let libpaths = libpaths.unwrap_or_else(|| {
+            handle
+                .path()
+                .resource_dir()
+                .map(|resource| vec![resource.join("lib").to_string_lossy().to_string()])
+                .unwrap_or_default()
+        });
+        add_libpath("LD_LIBRARY_PATH", &libpaths, true);
     }
 }
 
@@ -54,71 +119,427 @@ fn showfile(_: tauri::AppHandle, path: &str) {
     showfile::show_path_in_file_manager(path);
 }
 
-#[tauri::command]
-async fn load_settings(handle: tauri::AppHandle) -> Result<Options, String> {
-    let options: Options = if let Ok(mut file) =
-        File::open(get_settings_file_path(&handle).map_err(|e| e.to_string())?).await
-    {
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .await
-            .map_err(|e| e.to_string())?;
-        serde_json::from_str(&contents).unwrap_or_default()
-    } else {
-        Default::default()
+async fn read_settings(
+    path: &Path,
+    console_emu_input_tx: &Sender<ConsoleMessage>,
+) -> Result<Options, String> {
+    let Ok(mut file) = File::open(path).await else {
+        return Ok(Default::default());
+    };
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let value = serde_json::from_str::<serde_json::Value>(&contents);
+    let version = value.as_ref().map_or(0, Options::version);
+    let mut options = match value.and_then(Options::migrate) {
+        Ok(options) => options,
+        Err(e) => {
+            // keep the file aside instead of loading the defaults, which would overwrite it on save
+            let backup = corrupt_backup_path(path);
+            tokio::fs::copy(path, &backup)
+                .await
+                .map_err(|e| e.to_string())?;
+            let msg = format!(
+                "Failed to load {}: {}. The file is backed up to {}, please fix or remove it",
+                path.display(),
+                e,
+                backup.display()
+            );
+            let _ = console_emu_input_tx
+                .send(ConsoleMessage::Error(msg.clone()))
+                .await;
+            return Err(msg);
+        }
     };
+
+    if version < SETTINGS_VERSION {
+        // write_settings backs up the original file to settings.json.bak
+        write_settings(path, &options).await?;
+    } else if version > SETTINGS_VERSION {
+        let _ = console_emu_input_tx
+            .send(ConsoleMessage::Info(format!(
+                "{} was written by a newer version (settings version {}), it is loaded read-only",
+                path.display(),
+                version
+            )))
+            .await;
+        options.version = SETTINGS_VERSION;
+    }
     Ok(options)
 }
 
+fn corrupt_backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.corrupt")
+}
+
+async fn stored_settings_version(path: &Path) -> Option<u32> {
+    let contents = tokio::fs::read_to_string(path).await.ok()?;
+    let value: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    Some(Options::version(&value))
+}
+
+fn apply_console_history(handle: &tauri::AppHandle, options: &Options) {
+    if let Some(dropped) = handle
+        .state::<ConsoleHistory>()
+        .set_capacity(options.console_history)
+    {
+        let _ = handle.emit("console-truncated", dropped);
+    }
+}
+
 #[tauri::command]
-async fn save_settings(handle: tauri::AppHandle, options: &str) -> Result<(), String> {
-    let options: Options = serde_json::from_str(options).map_err(|e| e.to_string())?;
-    let json = serde_json::to_string_pretty(&options).map_err(|e| e.to_string())?;
-    let mut file = match OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(get_settings_file_path(&handle).map_err(|e| e.to_string())?)
+async fn load_settings(
+    handle: tauri::AppHandle,
+    console_emu_input_tx: tauri::State<'_, Sender<ConsoleMessage>>,
+) -> Result<Options, String> {
+    let options = read_settings(
+        &get_settings_file_path(&handle).map_err(|e| e.to_string())?,
+        &console_emu_input_tx,
+    )
+    .await?;
+    apply_console_history(&handle, &options);
+    Ok(options)
+}
+
+async fn write_settings(path: &Path, options: &Options) -> Result<(), String> {
+    if let Some(version) = stored_settings_version(path)
         .await
+        .filter(|&v| v > SETTINGS_VERSION)
     {
-        Ok(file) => file,
-        Err(_) => File::create(get_settings_file_path(&handle).map_err(|e| e.to_string())?)
-            .await
-            .map_err(|e| e.to_string())?,
+        return Err(format!(
+            "{} was written by a newer version (settings version {}) and is not overwritten",
+            path.display(),
+            version
+        ));
+    }
+
+    let json = serde_json::to_string_pretty(options).map_err(|e| e.to_string())?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    let write = async {
+        let mut file = File::create(&tmp_path).await?;
+        file.write_all(json.as_bytes()).await?;
+        file.sync_all().await
     };
-    file.write_all(json.as_bytes())
+    if let Err(e) = write.await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(e.to_string());
+    }
+
+    if path.exists() {
+        tokio::fs::copy(path, path.with_extension("json.bak"))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::fs::rename(&tmp_path, path)
         .await
         .map_err(|e| e.to_string())?;
+
     Ok(())
 }
 
 #[tauri::command]
-async fn wpcap_installed() -> bool {
-    #[cfg(target_os = "windows")]
+async fn save_settings(handle: tauri::AppHandle, options: &str) -> Result<(), String> {
+    let options: Options = serde_json::from_str(options).map_err(|e| e.to_string())?;
+    write_settings(
+        &get_settings_file_path(&handle).map_err(|e| e.to_string())?,
+        &options,
+    )
+    .await
+}
+
+#[derive(Serialize)]
+struct Profile {
+    name: String,
+    options: Options,
+}
+
+struct ActiveProfile(Mutex<String>);
+
+fn get_profile_file_path(handle: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_PROFILE {
+        return get_settings_file_path(handle).map_err(|e| e.to_string());
+    }
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ')
     {
-        unsafe {
-            if libloading::Library::new("wpcap.dll").is_err() {
-                return false;
+        return Err(format!("Invalid profile name: {}", name));
+    }
+    let mut path = get_profiles_dir_path(handle).map_err(|e| e.to_string())?;
+    path.push(format!("{}.json", name));
+    Ok(path)
+}
+
+#[tauri::command]
+async fn list_profiles(handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let mut profiles = Vec::new();
+    let mut entries =
+        tokio::fs::read_dir(get_profiles_dir_path(&handle).map_err(|e| e.to_string())?)
+            .await
+            .map_err(|e| e.to_string())?;
+    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                if name != DEFAULT_PROFILE {
+                    profiles.push(name.to_string());
+                }
             }
         }
     }
-    true
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(profiles)
+}
+
+#[tauri::command]
+async fn load_profile(
+    handle: tauri::AppHandle,
+    active_profile: tauri::State<'_, ActiveProfile>,
+    console_emu_input_tx: tauri::State<'_, Sender<ConsoleMessage>>,
+    name: &str,
+) -> Result<Profile, String> {
+    let path = get_profile_file_path(&handle, name)?;
+    if name != DEFAULT_PROFILE && !path.exists() {
+        return Err(format!("Profile not found: {}", name));
+    }
+    let options = read_settings(&path, &console_emu_input_tx).await?;
+    apply_console_history(&handle, &options);
+    *active_profile.0.lock().unwrap() = name.to_string();
+    Ok(Profile {
+        name: name.to_string(),
+        options,
+    })
+}
+
+#[tauri::command]
+async fn save_profile(
+    handle: tauri::AppHandle,
+    active_profile: tauri::State<'_, ActiveProfile>,
+    name: &str,
+    options: &str,
+) -> Result<String, String> {
+    let options: Options = serde_json::from_str(options).map_err(|e| e.to_string())?;
+    write_settings(&get_profile_file_path(&handle, name)?, &options).await?;
+    *active_profile.0.lock().unwrap() = name.to_string();
+    Ok(name.to_string())
+}
+
+#[tauri::command]
+fn get_console_history(history: tauri::State<'_, ConsoleHistory>) -> ConsoleHistorySnapshot {
+    history.snapshot()
+}
+
+#[tauri::command]
+async fn save_console_log(
+    history: tauri::State<'_, ConsoleHistory>,
+    path: &str,
+) -> Result<usize, String> {
+    if let Some((log_path, lines)) = history.log_file() {
+        tokio::fs::copy(&log_path, path)
+            .await
+            .map_err(|e| format!("Failed to write console log to {}: {}", path, e))?;
+        return Ok(lines);
+    }
+
+    // without the log file, only the lines left in the history can be saved
+    let (lines, dropped) = history.timestamped_lines();
+    let header = if dropped > 0 {
+        format!(
+            "[truncated: {} older lines are dropped from the console history]\n",
+            dropped
+        )
+    } else {
+        String::new()
+    };
+    let contents = lines
+        .iter()
+        .fold(header, |contents, line| contents + line + "\n");
+    tokio::fs::write(path, contents)
+        .await
+        .map_err(|e| format!("Failed to write console log to {}: {}", path, e))?;
+    Ok(lines.len())
+}
+
+#[tauri::command]
+fn active_profile(active_profile: tauri::State<'_, ActiveProfile>) -> String {
+    active_profile.0.lock().unwrap().clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+enum PcapDriver {
+    NotFound,
+    WinPcap,
+    Npcap,
+    NpcapWithoutWinPcapCompat,
+    Libpcap,
+}
+
+#[cfg(target_os = "windows")]
+fn pcap_driver() -> PcapDriver {
+    let npcap_dir = std::path::Path::new(&std::env::var("SystemRoot").unwrap_or_default())
+        .join("System32")
+        .join("Npcap");
+    unsafe {
+        if libloading::Library::new("wpcap.dll").is_ok() {
+            return if npcap_dir.exists() {
+                PcapDriver::Npcap
+            } else {
+                PcapDriver::WinPcap
+            };
+        }
+        if libloading::Library::new(npcap_dir.join("wpcap.dll")).is_ok() {
+            return PcapDriver::NpcapWithoutWinPcapCompat;
+        }
+    }
+    PcapDriver::NotFound
+}
+
+#[cfg(not(target_os = "windows"))]
+fn pcap_driver() -> PcapDriver {
+    PcapDriver::Libpcap
 }
 
 #[tauri::command]
-async fn twincat_installed() -> bool {
-    std::path::Path::new("C:/TwinCAT/3.1/Config/Io/EtherCAT").exists()
+async fn wpcap_installed() -> PcapDriver {
+    pcap_driver()
+}
+
+const DEFAULT_TWINCAT_DIR: &str = "C:/TwinCAT";
+const DEFAULT_XAE_SHELL_DIR: &str = "C:/Program Files (x86)/Beckhoff/TcXaeShell";
+
+fn ethercat_config_dir(twincat_options: &options::TwinCATOptions) -> Result<PathBuf, String> {
+    if twincat_options.twincat_dir.is_empty() {
+        return Ok(Path::new(DEFAULT_TWINCAT_DIR).join("3.1/Config/Io/EtherCAT"));
+    }
+    let dir = Path::new(&twincat_options.twincat_dir).join("3.1/Config/Io/EtherCAT");
+    if !dir.is_dir() {
+        return Err(format!(
+            "{} does not look like a TwinCAT installation directory: {} is not found",
+            twincat_options.twincat_dir,
+            dir.display()
+        ));
+    }
+    Ok(dir)
+}
+
+fn xae_shell_path(twincat_options: &options::TwinCATOptions) -> Result<PathBuf, String> {
+    if twincat_options.xae_shell_dir.is_empty() {
+        return Ok(Path::new(DEFAULT_XAE_SHELL_DIR).join("Common7/IDE/TcXaeShell.exe"));
+    }
+    let path = Path::new(&twincat_options.xae_shell_dir).join("Common7/IDE/TcXaeShell.exe");
+    if !path.is_file() {
+        return Err(format!(
+            "{} does not look like a TcXaeShell installation directory: {} is not found",
+            twincat_options.xae_shell_dir,
+            path.display()
+        ));
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+async fn twincat_installed(twincat_options: &str) -> Result<bool, String> {
+    let twincat_options: options::TwinCATOptions =
+        serde_json::from_str(twincat_options).map_err(|e| e.to_string())?;
+    Ok(ethercat_config_dir(&twincat_options).map_or(false, |dir| dir.exists()))
+}
+
+#[cfg(target_os = "windows")]
+fn is_elevated() -> bool {
+    unsafe {
+        libloading::Library::new("shell32.dll")
+            .and_then(|lib| {
+                let is_user_an_admin =
+                    lib.get::<unsafe extern "system" fn() -> i32>(b"IsUserAnAdmin")?;
+                Ok(is_user_an_admin() != 0)
+            })
+            .unwrap_or(false)
+    }
+}
+
+async fn check_write_access(dir: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    if !is_elevated() {
+        return Err(format!(
+            "Administrator privileges are required to write to {}. Please restart AUTD3 Server as administrator.",
+            dir.display()
+        ));
+    }
+
+    let probe = dir.join(".autd3-server-write-test");
+    File::create(&probe).await.map_err(|e| {
+        format!(
+            "No write access to {} ({}). Please restart AUTD3 Server as administrator.",
+            dir.display(),
+            e
+        )
+    })?;
+    let _ = tokio::fs::remove_file(&probe).await;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CopyProgress {
+    file: String,
+    copied: u64,
+    total: u64,
+}
+
+async fn copy_with_progress(
+    handle: &tauri::AppHandle,
+    src: &Path,
+    dst: &Path,
+) -> std::io::Result<()> {
+    let file = dst
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut reader = File::open(src).await?;
+    let total = reader.metadata().await?.len();
+    let mut writer = File::create(dst).await?;
+
+    let mut buf = vec![0u8; 8192];
+    let mut copied = 0;
+    loop {
+        let _ = handle.emit(
+            "copy-progress",
+            CopyProgress {
+                file: file.clone(),
+                copied,
+                total,
+            },
+        );
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        copied += n as u64;
+    }
+    writer.sync_all().await
 }
 
 #[tauri::command]
 async fn copy_autd_xml(
     handle: tauri::AppHandle,
-    console_emu_input_tx: tauri::State<'_, Sender<String>>,
+    console_emu_input_tx: tauri::State<'_, Sender<ConsoleMessage>>,
+    twincat_options: &str,
 ) -> Result<(), String> {
-    let dst = std::path::Path::new("C:/TwinCAT/3.1/Config/Io/EtherCAT/AUTD.xml");
+    let twincat_options: options::TwinCATOptions =
+        serde_json::from_str(twincat_options).map_err(|e| e.to_string())?;
+    let dst = ethercat_config_dir(&twincat_options)?.join("AUTD.xml");
 
     if dst.exists() {
         console_emu_input_tx
-            .send("AUTD.xml is already exists".to_string())
+            .send(ConsoleMessage::Info(
+                "AUTD.xml is already exists".to_string(),
+            ))
             .await
             .map_err(|e| e.to_string())?;
         return Ok(());
@@ -128,29 +549,124 @@ async fn copy_autd_xml(
         return Err("TwinCAT is not installed".to_string());
     }
 
+    if let Some(dir) = dst.parent() {
+        check_write_access(dir).await?;
+    }
+
     let autd_xml_path = handle
         .path()
         .resource_dir()
         .map(|resource| resource.join("TwinCATAUTDServer/AUTD.xml"))
         .map_err(|_| "Can't find AUTD.xml")?;
 
-    tokio::fs::copy(autd_xml_path, dst)
+    console_emu_input_tx
+        .send(ConsoleMessage::Info(format!(
+            "Copying AUTD.xml to {}",
+            dst.display()
+        )))
         .await
         .map_err(|e| e.to_string())?;
 
+    if let Err(e) = copy_with_progress(&handle, &autd_xml_path, &dst).await {
+        let _ = tokio::fs::remove_file(&dst).await;
+        console_emu_input_tx
+            .send(ConsoleMessage::Error(format!(
+                "Failed to copy AUTD.xml: {}",
+                e
+            )))
+            .await
+            .map_err(|e| e.to_string())?;
+        return Err(e.to_string());
+    }
+
     console_emu_input_tx
-        .send("AUTD.xml is successfully copied".to_string())
+        .send(ConsoleMessage::Info(
+            "AUTD.xml is successfully copied".to_string(),
+        ))
         .await
         .map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
+type KillSender = oneshot::Sender<oneshot::Sender<()>>;
+
+/// Running servers by name. Each registration gets a new id, so that the registration of
+/// a stopped server does not remove a restarted server with the same name.
+#[derive(Default)]
+struct ServerProcesses {
+    processes: Mutex<HashMap<String, (u64, KillSender)>>,
+    next_id: AtomicU64,
+}
+
+struct ServerRegistration<'a> {
+    processes: &'a ServerProcesses,
+    name: String,
+    id: u64,
+}
+
+impl Drop for ServerRegistration<'_> {
+    fn drop(&mut self) {
+        self.processes.unregister(&self.name, self.id);
+    }
+}
+
+impl ServerProcesses {
+    fn register(
+        &self,
+        name: &str,
+    ) -> Result<
+        (
+            ServerRegistration<'_>,
+            oneshot::Receiver<oneshot::Sender<()>>,
+        ),
+        String,
+    > {
+        let mut processes = self.processes.lock().unwrap();
+        if processes.contains_key(name) {
+            return Err(format!("{} is already running", name));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (kill_tx, kill_rx) = oneshot::channel();
+        processes.insert(name.to_string(), (id, kill_tx));
+        Ok((
+            ServerRegistration {
+                processes: self,
+                name: name.to_string(),
+                id,
+            },
+            kill_rx,
+        ))
+    }
+
+    fn unregister(&self, name: &str, id: u64) {
+        let mut processes = self.processes.lock().unwrap();
+        if processes
+            .get(name)
+            .is_some_and(|(current, _)| *current == id)
+        {
+            processes.remove(name);
+        }
+    }
+
+    async fn stop(&self, name: &str) -> bool {
+        let kill_tx = self.processes.lock().unwrap().remove(name);
+        if let Some((_, kill_tx)) = kill_tx {
+            let (done_tx, done_rx) = oneshot::channel();
+            if kill_tx.send(done_tx).is_ok() {
+                return done_rx.await.is_ok();
+            }
+        }
+        false
+    }
+}
+
 #[tauri::command]
 async fn run_twincat_server(
     twincat_options: &str,
     handle: tauri::AppHandle,
-    console_emu_input_tx: tauri::State<'_, Sender<String>>,
+    console_emu_input_tx: tauri::State<'_, Sender<ConsoleMessage>>,
+    processes: tauri::State<'_, ServerProcesses>,
 ) -> Result<(), String> {
     let twincat_autd_server_path = handle
         .path()
@@ -160,6 +676,9 @@ async fn run_twincat_server(
 
     let twincat_options: options::TwinCATOptions =
         serde_json::from_str(twincat_options).map_err(|e| e.to_string())?;
+    twincat_options.validate()?;
+
+    let (registration, mut kill_rx) = processes.register(TWINCAT_SERVER)?;
 
     let mut args = vec![
         "-c".to_string(),
@@ -192,34 +711,150 @@ async fn run_twincat_server(
     let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
     let mut reader = BufReader::new(stdout);
 
+    console_emu_input_tx
+        .send(ConsoleMessage::Started(TWINCAT_SERVER.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut done = None;
     loop {
         let mut buf = String::new();
-        if reader.read_line(&mut buf).await.unwrap() == 0 {
-            break;
+        tokio::select! {
+            n = reader.read_line(&mut buf) => {
+                match n {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        console_emu_input_tx
+                            .send(ConsoleMessage::Stdout(buf.trim().to_string()))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Err(e) => {
+                        let _ = child.kill().await;
+                        console_emu_input_tx
+                            .send(ConsoleMessage::Error(e.to_string()))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                        return Err(e.to_string());
+                    }
+                }
+            }
+            Ok(done_tx) = &mut kill_rx => {
+                child.kill().await.map_err(|e| e.to_string())?;
+                done = Some(done_tx);
+                break;
+            }
         }
-        console_emu_input_tx
-            .send(buf.trim().to_string())
-            .await
-            .map_err(|e| e.to_string())?;
     }
 
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    drop(registration);
+    if let Some(done_tx) = done {
+        let _ = done_tx.send(());
+    }
+    console_emu_input_tx
+        .send(ConsoleMessage::Exit(status.code()))
+        .await
+        .map_err(|e| e.to_string())?;
+
     Ok(())
 }
 
 #[tauri::command]
-async fn open_xae_shell() -> Result<(), String> {
+async fn stop_twincat_server(processes: tauri::State<'_, ServerProcesses>) -> Result<bool, String> {
+    Ok(processes.stop(TWINCAT_SERVER).await)
+}
+
+#[tauri::command]
+async fn run_server(
+    name: &str,
+    args: Vec<String>,
+    handle: tauri::AppHandle,
+    console_emu_input_tx: tauri::State<'_, Sender<ConsoleMessage>>,
+    processes: tauri::State<'_, ServerProcesses>,
+) -> Result<Option<i32>, String> {
+    if !SIDECAR_SERVERS.contains(&name) {
+        return Err(format!("Unknown server: {}", name));
+    }
+
+    let (registration, mut kill_rx) = processes.register(name)?;
+
+    let (mut rx, child) = handle
+        .shell()
+        .sidecar(name)
+        .and_then(|command| command.args(args).spawn())
+        .map_err(|e| e.to_string())?;
+
+    console_emu_input_tx
+        .send(ConsoleMessage::Started(name.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut child = Some(child);
+    let mut done = None;
+    let code = loop {
+        tokio::select! {
+            event = rx.recv() => {
+                match event {
+                    Some(CommandEvent::Stdout(line)) | Some(CommandEvent::Stderr(line)) => {
+                        console_emu_input_tx
+                            .send(ConsoleMessage::Stdout(
+                                String::from_utf8_lossy(&line).trim().to_string(),
+                            ))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Some(CommandEvent::Error(e)) => {
+                        console_emu_input_tx
+                            .send(ConsoleMessage::Error(e))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Some(CommandEvent::Terminated(payload)) => break payload.code,
+                    Some(_) => {}
+                    None => break None,
+                }
+            }
+            Ok(done_tx) = &mut kill_rx, if child.is_some() => {
+                if let Some(child) = child.take() {
+                    child.kill().map_err(|e| e.to_string())?;
+                }
+                done = Some(done_tx);
+            }
+        }
+    };
+
+    drop(registration);
+    if let Some(done_tx) = done {
+        let _ = done_tx.send(());
+    }
+    console_emu_input_tx
+        .send(ConsoleMessage::Exit(code))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(code)
+}
+
+#[tauri::command]
+async fn stop_server(
+    name: &str,
+    processes: tauri::State<'_, ServerProcesses>,
+) -> Result<bool, String> {
+    Ok(processes.stop(name).await)
+}
+
+#[tauri::command]
+async fn open_xae_shell(twincat_options: &str) -> Result<(), String> {
+    let twincat_options: options::TwinCATOptions =
+        serde_json::from_str(twincat_options).map_err(|e| e.to_string())?;
+
     let path = std::env::var("TEMP").unwrap_or_default();
     let path = std::path::Path::new(&path)
         .join("TwinCATAUTDServer")
         .join("TwinCATAUTDServer.sln");
 
-    let xae_shell = std::path::Path::new("C:\\")
-        .join("Program Files (x86)")
-        .join("Beckhoff")
-        .join("TcXaeShell")
-        .join("Common7")
-        .join("IDE")
-        .join("TcXaeShell.exe");
+    let xae_shell = xae_shell_path(&twincat_options)?;
 
     if path.exists() {
         Command::new(&xae_shell).arg(&path).spawn()
@@ -238,7 +873,7 @@ async fn open_xae_shell() -> Result<(), String> {
 async fn main() {
     tauri::async_runtime::set(tokio::runtime::Handle::current());
 
-    let (console_emu_input_tx, mut console_emu_input_rx) = channel::<String>(32);
+    let (console_emu_input_tx, mut console_emu_input_rx) = channel::<ConsoleMessage>(32);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_os::init())
@@ -247,6 +882,9 @@ async fn main() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(console_emu_input_tx)
+        .manage(ActiveProfile(Mutex::new(DEFAULT_PROFILE.to_string())))
+        .manage(ServerProcesses::default())
+        .manage(ConsoleHistory::new(DEFAULT_CONSOLE_HISTORY))
         .setup(|app| {
             #[cfg(debug_assertions)]
             {
@@ -255,10 +893,22 @@ async fn main() {
                 window.close_devtools();
             }
 
+            if let Ok(dir) = app.path().app_log_dir() {
+                // the console log is saved from the history if the file cannot be created
+                let _ = std::fs::create_dir_all(&dir).and_then(|_| {
+                    app.state::<ConsoleHistory>()
+                        .set_log_file(dir.join(CONSOLE_LOG_PATH))
+                });
+            }
+
             let app_handle = app.handle().clone();
             tokio::spawn(async move {
-                while let Some(s) = console_emu_input_rx.recv().await {
-                    app_handle.emit("console-emu", s).unwrap();
+                while let Some(msg) = console_emu_input_rx.recv().await {
+                    if let Some(dropped) = app_handle.state::<ConsoleHistory>().push(msg.text()) {
+                        app_handle.emit("console-truncated", dropped).unwrap();
+                    }
+                    app_handle.emit("console-emu", msg.text()).unwrap();
+                    app_handle.emit("console-status", msg).unwrap();
                 }
             });
 
@@ -269,8 +919,17 @@ async fn main() {
             showfile,
             load_settings,
             save_settings,
+            list_profiles,
+            load_profile,
+            save_profile,
+            active_profile,
+            get_console_history,
+            save_console_log,
             copy_autd_xml,
             run_twincat_server,
+            stop_twincat_server,
+            run_server,
+            stop_server,
             open_xae_shell,
             twincat_installed,
             wpcap_installed
@@ -278,3 +937,171 @@ async fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("autd3-server-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn console_log_keeps_dropped_lines() {
+        let dir = temp_dir("console");
+        let path = dir.join(CONSOLE_LOG_PATH);
+        let history = ConsoleHistory::new(2);
+        history.push("first".to_string());
+        history.set_log_file(path.clone()).unwrap();
+        history.push("second".to_string());
+        history.push("third".to_string());
+
+        let (lines, dropped) = history.timestamped_lines();
+        assert_eq!(2, lines.len());
+        assert_eq!(1, dropped);
+        assert_eq!(Some((path.clone(), 3)), history.log_file());
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(3, log.lines().count());
+        assert!(log
+            .lines()
+            .zip(["first", "second", "third"])
+            .all(|(l, s)| l.ends_with(s)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_registration_keeps_restarted_server() {
+        let processes = ServerProcesses::default();
+
+        let (old, old_kill_rx) = processes.register("simulator").unwrap();
+        assert!(processes.register("simulator").is_err());
+
+        // the old server is stopped, and a new one is started before the old registration is dropped
+        drop(old_kill_rx);
+        assert!(!processes.stop("simulator").await);
+        let (new, _new_kill_rx) = processes.register("simulator").unwrap();
+        drop(old);
+        assert!(processes.register("simulator").is_err());
+
+        drop(new);
+        assert!(processes.register("simulator").is_ok());
+    }
+
+    #[tokio::test]
+    async fn read_settings_migrates_older_version() {
+        let dir = temp_dir("migrate");
+        let path = dir.join(SETTINGS_PATH);
+        std::fs::write(&path, r#"{"console_history": 10}"#).unwrap();
+        let (tx, _rx) = channel(1);
+
+        let options = read_settings(&path, &tx).await.unwrap();
+
+        assert_eq!(SETTINGS_VERSION, options.version);
+        assert_eq!(10, options.console_history);
+        assert_eq!(
+            r#"{"console_history": 10}"#,
+            std::fs::read_to_string(path.with_extension("json.bak")).unwrap()
+        );
+        assert_eq!(Some(SETTINGS_VERSION), stored_settings_version(&path).await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_settings_reports_corrupt_file() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join(SETTINGS_PATH);
+        for contents in [
+            "",
+            r#"{"console_history": "#,
+            r#"{"console_history": "10"}"#,
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            let (tx, mut rx) = channel(1);
+
+            assert!(read_settings(&path, &tx).await.is_err());
+            assert!(matches!(rx.try_recv(), Ok(ConsoleMessage::Error(_))));
+            assert_eq!(contents, std::fs::read_to_string(&path).unwrap());
+            assert_eq!(
+                contents,
+                std::fs::read_to_string(corrupt_backup_path(&path)).unwrap()
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_settings_keeps_newer_version() {
+        let dir = temp_dir("newer");
+        let path = dir.join(SETTINGS_PATH);
+        let contents = format!(
+            r#"{{"version": {}, "console_history": 10, "unknown": true}}"#,
+            SETTINGS_VERSION + 1
+        );
+        std::fs::write(&path, &contents).unwrap();
+        let (tx, mut rx) = channel(1);
+
+        let options = read_settings(&path, &tx).await.unwrap();
+
+        assert_eq!(10, options.console_history);
+        assert!(matches!(rx.try_recv(), Ok(ConsoleMessage::Info(_))));
+        assert!(write_settings(&path, &options).await.is_err());
+        assert_eq!(contents, std::fs::read_to_string(&path).unwrap());
+        assert!(!path.with_extension("json.bak").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_settings_keeps_backup() {
+        let dir = temp_dir("backup");
+        let path = dir.join(SETTINGS_PATH);
+        let original = serde_json::to_string_pretty(&Options::default()).unwrap();
+        std::fs::write(&path, &original).unwrap();
+
+        let options = Options {
+            console_history: 10,
+            ..Default::default()
+        };
+        write_settings(&path, &options).await.unwrap();
+
+        assert_eq!(
+            original,
+            std::fs::read_to_string(path.with_extension("json.bak")).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string_pretty(&options).unwrap(),
+            std::fs::read_to_string(&path).unwrap()
+        );
+        assert!(!path.with_extension("json.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_settings_failure_keeps_original() {
+        let dir = temp_dir("failure");
+        let path = dir.join(SETTINGS_PATH);
+        let original = serde_json::to_string_pretty(&Options::default()).unwrap();
+        std::fs::write(&path, &original).unwrap();
+        // a directory in place of the temporary file makes the write fail
+        std::fs::create_dir(path.with_extension("json.tmp")).unwrap();
+
+        let options = Options {
+            console_history: 10,
+            ..Default::default()
+        };
+        assert!(write_settings(&path, &options).await.is_err());
+
+        assert_eq!(original, std::fs::read_to_string(&path).unwrap());
+        assert!(!path.with_extension("json.bak").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
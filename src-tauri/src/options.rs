@@ -2,7 +2,12 @@ use serde::{Deserialize, Serialize};
 
 use autd3_link_soem::TimerStrategy;
 
+use crate::console::DEFAULT_CONSOLE_HISTORY;
+
+pub const SETTINGS_VERSION: u32 = 1;
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TwinCATOptions {
     pub client: String,
     pub sync0: u32,
@@ -11,6 +16,8 @@ pub struct TwinCATOptions {
     pub keep: bool,
     pub lightweight: bool,
     pub lightweight_port: u16,
+    pub twincat_dir: String,
+    pub xae_shell_dir: String,
 }
 
 impl Default for TwinCATOptions {
@@ -23,11 +30,56 @@ impl Default for TwinCATOptions {
             keep: false,
             lightweight: false,
             lightweight_port: 8080,
+            twincat_dir: "".to_string(),
+            xae_shell_dir: "".to_string(),
+        }
+    }
+}
+
+impl TwinCATOptions {
+    // in units of 500us. TwinCATAUTDServer converts the sync0 cycle time to ns in a 32-bit int
+    // (500000 ns per unit, see TwinCATAUTDServer/Program.cs), so a larger value overflows there.
+    // The task and base times are converted to 100 ns units (5000 per unit) and are bounded by the same value.
+    const MAX_CYCLE: u32 = i32::MAX as u32 / 500_000;
+
+    fn to_us(name: &str, value: u32) -> Result<u32, String> {
+        value
+            .checked_mul(500)
+            .ok_or_else(|| format!("{} ({} x 500us) is out of range", name, value))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        [
+            ("Sync0 cycle time", self.sync0),
+            ("Send task cycle time", self.task),
+            ("CPU base time", self.base),
+        ]
+        .into_iter()
+        .try_for_each(|(name, value)| {
+            if (1..=Self::MAX_CYCLE).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} must be between 500us and {}us, but got {}us",
+                    name,
+                    Self::MAX_CYCLE * 500,
+                    Self::to_us(name, value)?
+                ))
+            }
+        })?;
+        if self.task % self.base != 0 {
+            return Err(format!(
+                "Send task cycle time ({}us) must be a multiple of CPU base time ({}us)",
+                self.task * 500,
+                self.base * 500
+            ));
         }
+        Ok(())
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SOEMOptions {
     pub ifname: String,
     pub port: u16,
@@ -59,6 +111,7 @@ impl Default for SOEMOptions {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SimulatorOptions {
     pub vsync: bool,
     pub port: u16,
@@ -81,9 +134,121 @@ impl Default for SimulatorOptions {
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Options {
+    pub version: u32,
     pub twincat: TwinCATOptions,
     pub soem: SOEMOptions,
     pub simulator: SimulatorOptions,
+    pub console_history: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            twincat: Default::default(),
+            soem: Default::default(),
+            simulator: Default::default(),
+            console_history: DEFAULT_CONSOLE_HISTORY,
+        }
+    }
+}
+
+impl Options {
+    pub fn version(value: &serde_json::Value) -> u32 {
+        value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0) as u32
+    }
+
+    pub fn migrate(mut value: serde_json::Value) -> serde_json::Result<Self> {
+        let version = Self::version(&value);
+        if let Some(obj) = value.as_object_mut() {
+            if version < 1 {
+                // v0 has no version field, otherwise it has the same layout as v1
+                obj.insert("version".to_string(), 1.into());
+            }
+        }
+        serde_json::from_value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twincat(sync0: u32, task: u32, base: u32) -> TwinCATOptions {
+        TwinCATOptions {
+            sync0,
+            task,
+            base,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn twincat_validate_bounds() {
+        assert_eq!(Ok(()), twincat(1, 1, 1).validate());
+        assert_eq!(2147000, TwinCATOptions::MAX_CYCLE * 500);
+        assert_eq!(
+            Ok(()),
+            twincat(
+                TwinCATOptions::MAX_CYCLE,
+                TwinCATOptions::MAX_CYCLE,
+                TwinCATOptions::MAX_CYCLE
+            )
+            .validate()
+        );
+        assert_eq!(
+            Err("Sync0 cycle time must be between 500us and 2147000us, but got 0us".to_string()),
+            twincat(0, 1, 1).validate()
+        );
+        assert_eq!(
+            Err(
+                "Send task cycle time must be between 500us and 2147000us, but got 2147500us"
+                    .to_string()
+            ),
+            twincat(1, TwinCATOptions::MAX_CYCLE + 1, 1).validate()
+        );
+        assert_eq!(
+            Err(format!(
+                "CPU base time must be between 500us and 2147000us, but got {}us",
+                u32::MAX / 500 * 500
+            )),
+            twincat(1, 1, u32::MAX / 500).validate()
+        );
+    }
+
+    #[test]
+    fn twincat_validate_overflow() {
+        assert_eq!(
+            Err(format!(
+                "Sync0 cycle time ({} x 500us) is out of range",
+                u32::MAX / 500 + 1
+            )),
+            twincat(u32::MAX / 500 + 1, 1, 1).validate()
+        );
+        assert_eq!(
+            Err(format!(
+                "Send task cycle time ({} x 500us) is out of range",
+                u32::MAX
+            )),
+            twincat(1, u32::MAX, 1).validate()
+        );
+    }
+
+    #[test]
+    fn twincat_validate_multiple() {
+        assert_eq!(Ok(()), twincat(1, 4, 2).validate());
+        assert_eq!(
+            Err(
+                "Send task cycle time (1500us) must be a multiple of CPU base time (1000us)"
+                    .to_string()
+            ),
+            twincat(1, 3, 2).validate()
+        );
+    }
 }
@@ -14,6 +14,8 @@ export interface TwinCATOptions {
     keep: boolean;
     lightweight: boolean;
     lightweight_port: number;
+    twincat_dir: string;
+    xae_shell_dir: string;
 }
 
 export interface SOEMOptions {
@@ -39,7 +41,9 @@ export interface SimulatorOptions {
 }
 
 export interface Options {
+    version: number;
     twincat: TwinCATOptions;
     soem: SOEMOptions;
     simulator: SimulatorOptions;
+    console_history: number;
 }
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use spdx::{Expression, LicenseReq, ParseMode};
+
+use crate::deps::Dependency;
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// SPDX identifiers that are acceptable. Empty means any license not denied.
+    pub allow: Vec<String>,
+    /// SPDX identifiers that are never acceptable.
+    pub deny: Vec<String>,
+    /// Packages that have been reviewed manually and are skipped.
+    pub exceptions: Vec<String>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn is_allowed(&self, req: &LicenseReq) -> bool {
+        let id = match req.license.id() {
+            Some(id) => id.name,
+            None => return false,
+        };
+        !self.deny.iter().any(|d| d == id)
+            && (self.allow.is_empty() || self.allow.iter().any(|a| a == id))
+    }
+
+    pub fn check(&self, dep: &Dependency) -> Result<(), String> {
+        if self.exceptions.contains(&dep.name) {
+            return Ok(());
+        }
+        let license = dep.license.as_deref().ok_or("missing license")?;
+        let expr = Expression::parse_mode(license, ParseMode::LAX)
+            .map_err(|e| format!("invalid license expression \"{}\": {}", license, e))?;
+        if expr.evaluate(|req| self.is_allowed(req)) {
+            Ok(())
+        } else {
+            Err(format!("license \"{}\" is not allowed", license))
+        }
+    }
+}
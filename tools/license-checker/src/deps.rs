@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+};
+
+use cargo_metadata::{DependencyKind, MetadataCommand};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Dependency {
+    pub name: String,
+    pub version: Option<String>,
+    pub license: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Dependency {
+    pub fn external(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: None,
+            license: None,
+            url: Some(url.to_owned()),
+        }
+    }
+}
+
+pub fn get_rs_deps(manifest_path: impl AsRef<Path>) -> anyhow::Result<Vec<Dependency>> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path.as_ref())
+        .exec()?;
+    let root = metadata
+        .root_package()
+        .ok_or_else(|| anyhow::anyhow!("No root package in {:?}", manifest_path.as_ref()))?;
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Failed to resolve dependencies of {}", root.name))?;
+    let nodes = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node))
+        .collect::<HashMap<_, _>>();
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([&root.id]);
+    while let Some(id) = queue.pop_front() {
+        if !visited.insert(id) {
+            continue;
+        }
+        if let Some(node) = nodes.get(id) {
+            queue.extend(
+                node.deps
+                    .iter()
+                    .filter(|dep| {
+                        dep.dep_kinds
+                            .iter()
+                            .any(|info| info.kind != DependencyKind::Development)
+                    })
+                    .map(|dep| &dep.pkg),
+            );
+        }
+    }
+
+    let mut deps = metadata
+        .packages
+        .iter()
+        .filter(|package| package.id != root.id && visited.contains(&package.id))
+        .filter(|package| !metadata.workspace_members.contains(&package.id))
+        .map(|package| Dependency {
+            name: package.name.clone(),
+            version: Some(package.version.to_string()),
+            license: package.license.clone(),
+            url: package.repository.clone().or(package.homepage.clone()),
+        })
+        .collect::<Vec<_>>();
+    deps.sort();
+    Ok(deps)
+}
+
+pub fn get_npm_deps(package_lock: impl AsRef<Path>) -> anyhow::Result<Vec<Dependency>> {
+    let lock: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(package_lock.as_ref())?)?;
+    let packages = lock["packages"]
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("No packages in {:?}", package_lock.as_ref()))?;
+
+    let mut deps = packages
+        .iter()
+        .filter(|(path, _)| !path.is_empty())
+        .filter(|(_, package)| !package["dev"].as_bool().unwrap_or(false))
+        .map(|(path, package)| Dependency {
+            name: package["name"]
+                .as_str()
+                .or_else(|| path.rsplit("node_modules/").next())
+                .unwrap_or(path)
+                .to_owned(),
+            version: package["version"].as_str().map(str::to_owned),
+            license: package["license"].as_str().map(str::to_owned),
+            url: package["resolved"].as_str().map(str::to_owned),
+        })
+        .collect::<Vec<_>>();
+    deps.sort();
+    deps.dedup();
+    Ok(deps)
+}
@@ -1,39 +1,66 @@
-use std::path::Path;
+mod check;
+mod deps;
+mod output;
 
-fn main() -> anyhow::Result<()> {
-    let license_file_map = Vec::new();
+use std::path::{Path, PathBuf};
 
-    let changed = autd3_license_check::check(
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../simulator/Cargo.toml"),
-        "ThirdPartyNotice",
-        &license_file_map,
-        &[],
-    )?;
+use clap::{Parser, ValueEnum};
+use deps::Dependency;
 
-    let changed = autd3_license_check::check(
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../SOEMAUTDServer/Cargo.toml"),
-        "ThirdPartyNotice",
-        &license_file_map,
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// ThirdPartyNotice.txt
+    Notice,
+    /// JSON array of {name, version, license, url}
+    Json,
+    /// SPDX 2.3 JSON document
+    Spdx,
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Arg {
+    /// Output format
+    #[clap(long, default_value = "notice")]
+    format: Format,
+    /// Exit with an error if any dependency has a missing or disallowed license
+    #[clap(long)]
+    check: bool,
+    /// License policy used by --check
+    #[clap(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/licenses.toml"))]
+    config: PathBuf,
+}
+
+const CRATES: [(&str, &[(&str, &str)]); 4] = [
+    ("simulator", &[]),
+    (
+        "SOEMAUTDServer",
         &[("SOEM", "SOEM\nhttps://github.com/OpenEtherCATsociety/SOEM")],
-    )? || changed;
+    ),
+    ("TwinCATAUTDServerLightweight", &[]),
+    ("src-tauri", &[]),
+];
 
-    let changed = autd3_license_check::check(
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../TwinCATAUTDServerLightweight/Cargo.toml"),
-        "ThirdPartyNotice",
-        &license_file_map,
-        &[],
-    )? || changed;
+fn root_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
 
-    let changed = autd3_license_check::check(
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../src-tauri/Cargo.toml"),
-        "ThirdPartyNotice",
-        &license_file_map,
-        &[],
-    )? || changed;
+fn notice() -> anyhow::Result<()> {
+    let license_file_map = Vec::new();
+
+    let mut changed = false;
+    for (dir, extra) in CRATES {
+        changed = autd3_license_check::check(
+            root_dir().join(dir).join("Cargo.toml"),
+            "ThirdPartyNotice",
+            &license_file_map,
+            extra,
+        )? || changed;
+    }
 
     let changed = autd3_license_check::check_npm(
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../node_modules"),
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../package-lock.json"),
+        root_dir().join("node_modules"),
+        root_dir().join("package-lock.json"),
         "ThirdPartyNotice",
     )? || changed;
 
@@ -45,3 +72,77 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn collect() -> anyhow::Result<Vec<(PathBuf, String, Vec<Dependency>)>> {
+    let mut targets = CRATES
+        .into_iter()
+        .map(|(dir, extra)| {
+            let mut deps = deps::get_rs_deps(root_dir().join(dir).join("Cargo.toml"))?;
+            deps.extend(extra.iter().map(|(name, text)| {
+                Dependency::external(name, text.lines().last().unwrap_or_default())
+            }));
+            Ok((root_dir().join(dir), dir.to_owned(), deps))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    targets.push((
+        root_dir(),
+        "autd3-server".to_owned(),
+        deps::get_npm_deps(root_dir().join("package-lock.json"))?,
+    ));
+    Ok(targets)
+}
+
+fn check(config: &Path) -> anyhow::Result<()> {
+    let config = check::Config::load(config)?;
+
+    let mut violations = Vec::new();
+    for (_, name, deps) in collect()? {
+        for dep in deps {
+            if let Err(e) = config.check(&dep) {
+                violations.push(format!(
+                    "{}: {} {}: {}",
+                    name,
+                    dep.name,
+                    dep.version.as_deref().unwrap_or_default(),
+                    e
+                ));
+            }
+        }
+    }
+    violations.sort();
+    violations.dedup();
+
+    if !violations.is_empty() {
+        violations.iter().for_each(|v| eprintln!("{}", v));
+        return Err(anyhow::anyhow!(
+            "{} dependencies have a missing or disallowed license",
+            violations.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let arg = Arg::parse();
+
+    if arg.check {
+        return check(&arg.config);
+    }
+
+    match arg.format {
+        Format::Notice => notice()?,
+        Format::Json => {
+            for (dir, _, deps) in collect()? {
+                output::write_json(dir.join("ThirdPartyNotice.json"), &deps)?;
+            }
+        }
+        Format::Spdx => {
+            for (dir, name, deps) in collect()? {
+                output::write_spdx(dir.join("ThirdPartyNotice.spdx.json"), &name, &deps)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::json;
+
+use crate::deps::Dependency;
+
+const NOASSERTION: &str = "NOASSERTION";
+
+pub fn write_json(path: impl AsRef<Path>, deps: &[Dependency]) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(deps)?)?;
+    Ok(())
+}
+
+pub fn write_spdx(path: impl AsRef<Path>, name: &str, deps: &[Dependency]) -> anyhow::Result<()> {
+    let packages = deps
+        .iter()
+        .enumerate()
+        .map(|(i, dep)| {
+            json!({
+                "SPDXID": format!("SPDXRef-Package-{}", i),
+                "name": dep.name,
+                "versionInfo": dep.version.as_deref().unwrap_or(NOASSERTION),
+                "downloadLocation": dep.url.as_deref().unwrap_or(NOASSERTION),
+                "licenseConcluded": NOASSERTION,
+                "licenseDeclared": dep.license.as_deref().unwrap_or(NOASSERTION),
+                "copyrightText": NOASSERTION,
+                "filesAnalyzed": false,
+            })
+        })
+        .collect::<Vec<_>>();
+    let doc = json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!("https://github.com/shinolab/autd3-server/spdx/{}-{}", name, env!("CARGO_PKG_VERSION")),
+        "creationInfo": {
+            "created": timestamp()?,
+            "creators": [format!("Tool: license-checker-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+    });
+    std::fs::write(path, serde_json::to_string_pretty(&doc)?)?;
+    Ok(())
+}
+
+fn timestamp() -> anyhow::Result<String> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // civil_from_days (http://howardhinnant.github.io/date_algorithms.html)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    ))
+}